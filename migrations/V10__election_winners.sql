@@ -0,0 +1 @@
+UPDATE elections SET address = jsonb_build_array(address) WHERE jsonb_typeof(address) = 'string';
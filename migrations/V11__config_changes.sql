@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS config_changes (
+    id SERIAL PRIMARY KEY,
+    call TEXT,
+    signer TEXT,
+    round OID,
+    block OID,
+    args TEXT
+);
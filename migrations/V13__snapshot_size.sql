@@ -0,0 +1,2 @@
+ALTER TABLE elections ADD COLUMN IF NOT EXISTS snapshot_voters OID;
+ALTER TABLE elections ADD COLUMN IF NOT EXISTS snapshot_targets OID;
@@ -0,0 +1,3 @@
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ;
+ALTER TABLE elections ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ;
+ALTER TABLE slashed ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ;
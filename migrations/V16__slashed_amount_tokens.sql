@@ -0,0 +1,2 @@
+ALTER TABLE slashed RENAME COLUMN amount TO amount_planck;
+ALTER TABLE slashed ADD COLUMN IF NOT EXISTS amount_tokens TEXT;
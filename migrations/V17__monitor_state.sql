@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS monitor_state (
+    id SMALLINT PRIMARY KEY,
+    state JSONB,
+    updated_at TIMESTAMPTZ
+);
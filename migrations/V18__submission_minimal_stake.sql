@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS submissions_minimal_stake ON submissions (((score->>'minimal_stake')::NUMERIC));
@@ -0,0 +1,5 @@
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS blocks_to_finalize OID;
+
+UPDATE submissions s SET blocks_to_finalize = (e.block::BIGINT - s.block::BIGINT)::OID
+FROM elections e
+WHERE e.round = s.round AND e.result != 'election failed' AND s.block <= e.block;
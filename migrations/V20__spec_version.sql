@@ -0,0 +1,2 @@
+ALTER TABLE elections ADD COLUMN IF NOT EXISTS spec_version OID;
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS spec_version OID;
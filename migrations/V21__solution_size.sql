@@ -0,0 +1,2 @@
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS solution_voters OID;
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS solution_winners OID;
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS ejections (
+    id SERIAL PRIMARY KEY,
+    address TEXT,
+    ejected_by TEXT,
+    round OID,
+    block OID
+);
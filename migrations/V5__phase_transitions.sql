@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS phase_transitions (
+    id SERIAL PRIMARY KEY,
+    round OID,
+    block OID,
+    from_phase TEXT,
+    to_phase TEXT
+);
@@ -0,0 +1,2 @@
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS extrinsic_hash TEXT;
+CREATE INDEX IF NOT EXISTS submissions_extrinsic_hash_idx ON submissions (extrinsic_hash);
@@ -0,0 +1 @@
+ALTER TABLE elections ADD COLUMN IF NOT EXISTS phase_durations JSONB;
@@ -0,0 +1,121 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::db::Database;
+use crate::helpers::{get_block, get_finalized_number, get_round};
+use crate::monitor::process_block;
+use crate::types::HeaderT;
+use crate::types::{Client, ElectionRound};
+use crate::{ingestion, prometheus, LOG_TARGET};
+use std::time::Instant;
+use tokio::task::JoinSet;
+
+/// The number of blocks after which the finalized head is queried again for the lag metric.
+const FINALIZED_HEAD_REFRESH_BLOCKS: u64 = 100;
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct BackfillOpts {
+    /// The first block to read.
+    #[clap(long)]
+    from: u64,
+    /// The last block to read, defaults to the most recent finalized block.
+    #[clap(long)]
+    to: Option<u64>,
+    /// After an election was finalized without a `Rewarded` event, wait for this many further
+    /// blocks to be finalized and read their `Rewarded` events before concluding the election was unsigned.
+    #[clap(long, default_value_t = 0, env = "UNSIGNED_GRACE_BLOCKS")]
+    unsigned_grace_blocks: u32,
+}
+
+/// Read the blocks `from..=to` and write their election data to the database.
+///
+/// The node must still have the state of these blocks, i.e. be an archive node for old blocks.
+/// The blocks of rounds that already have an election in the database are skipped.
+pub async fn run(client: Client, db: Database, opts: BackfillOpts) -> anyhow::Result<()> {
+    let BackfillOpts {
+        from,
+        to,
+        unsigned_grace_blocks,
+    } = opts;
+
+    let to = match to {
+        Some(to) => to,
+        None => get_finalized_number(client.rpc()).await?,
+    };
+
+    if from > to {
+        return Err(anyhow::anyhow!("--from={from} must not be after --to={to}"));
+    }
+
+    let mut state = ElectionRound::new();
+    read_blocks(&client, &db, &mut state, from, to, unsigned_grace_blocks).await
+}
+
+/// Read the blocks `from..=to` in order with the round `state`, which is
+/// kept to continue with the blocks after `to`.
+///
+/// The blocks of rounds that already have an election and the blocks `state`
+/// processed already are skipped, so their rows aren't inserted twice.
+pub async fn read_blocks(
+    client: &Client,
+    db: &Database,
+    state: &mut ElectionRound,
+    from: u64,
+    to: u64,
+    unsigned_grace_blocks: u32,
+) -> anyhow::Result<()> {
+    let mut finalized = to;
+    let mut late_rewards = JoinSet::new();
+    // The most recent round and whether it has an election in the database.
+    let mut recorded: Option<(u32, bool)> = None;
+
+    for n in from..=to {
+        if (n - from) % FINALIZED_HEAD_REFRESH_BLOCKS == 0 {
+            finalized = get_finalized_number(client.rpc()).await?;
+        }
+
+        let block = get_block(client, n).await?;
+
+        let round = get_round(client, block.hash()).await?;
+        let has_election = match recorded {
+            Some((r, has_election)) if r == round => has_election,
+            _ => {
+                let has_election = db.get_election_for_round(round).await?.is_some();
+                if has_election {
+                    tracing::info!(target: LOG_TARGET, round, "The round is already in the database, skipping its blocks");
+                }
+                recorded = Some((round, has_election));
+                has_election
+            }
+        };
+        if has_election || state.is_processed(n, block.hash()) {
+            // Otherwise the skipped blocks are reported as gaps and processed after all.
+            ingestion::record_processed_block(block.number());
+            continue;
+        }
+
+        let now = Instant::now();
+        process_block(
+            client,
+            db,
+            state,
+            &block,
+            unsigned_grace_blocks,
+            &mut late_rewards,
+        )
+        .await?;
+        prometheus::record_block_processing(now.elapsed());
+        prometheus::record_block_lag(
+            u32::try_from(finalized.saturating_sub(n)).unwrap_or(u32::MAX),
+        );
+    }
+
+    while let Some(res) = late_rewards.join_next().await {
+        res??;
+    }
+
+    tracing::info!(target: LOG_TARGET, "Backfilled blocks {from}..={to}");
+
+    Ok(())
+}
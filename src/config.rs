@@ -0,0 +1,104 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use anyhow::Context;
+use clap::{Command, CommandFactory};
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// The env var of the config file, if `--config` isn't given.
+const CONFIG_ENV: &str = "CONFIG";
+
+/// Build the command of `T` with the defaults of its options read from the config file, if any.
+///
+/// The config file is a TOML file whose keys are the names of the flags, e.g.
+/// `postgres-pool-size = 8`. The values become the defaults of the options, so the
+/// command line arguments and the env vars take precedence over the file.
+pub fn command<T: CommandFactory>(args: &[OsString]) -> anyhow::Result<Command> {
+    let mut cmd = T::command();
+    let Some(path) = config_path(args) else {
+        return Ok(cmd);
+    };
+
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read the config file {}", path.display()))?;
+    let config: toml::Table = content
+        .parse()
+        .with_context(|| format!("Invalid config file {}", path.display()))?;
+
+    for (key, value) in config {
+        let value = arg_value(&value)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported value of `{key}` in the config file"))?;
+        let found;
+        (cmd, found) = set_default(cmd, &key, value);
+        if !found {
+            anyhow::bail!("Unknown option `{key}` in the config file");
+        }
+    }
+
+    Ok(cmd)
+}
+
+/// The value of `--config` in `args` or of the `CONFIG` env var.
+fn config_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    std::env::var_os(CONFIG_ENV).map(PathBuf::from)
+}
+
+/// The value as it would be given on the command line, arrays are comma-separated.
+fn arg_value(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(n) => Some(n.to_string()),
+        toml::Value::Float(n) => Some(n.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        toml::Value::Array(values) => values
+            .iter()
+            .map(arg_value)
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.join(",")),
+        toml::Value::Datetime(_) | toml::Value::Table(_) => None,
+    }
+}
+
+/// Set the default of the option with the long name `name` in `cmd` and all of its subcommands.
+///
+/// Returns whether the option was found in any of them.
+fn set_default(mut cmd: Command, name: &str, value: String) -> (Command, bool) {
+    let mut found = false;
+
+    let id = cmd
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(name))
+        .map(|arg| arg.get_id().clone());
+    if let Some(id) = id {
+        // A default doesn't satisfy a required option otherwise.
+        cmd = cmd.mut_arg(id, |arg| arg.default_value(value.clone()).required(false));
+        found = true;
+    }
+
+    let subcommands: Vec<String> = cmd
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    for sub in subcommands {
+        cmd = cmd.mut_subcommand(sub, |sub| {
+            let (sub, found_in_sub) = set_default(sub, name, value.clone());
+            found |= found_in_sub;
+            sub
+        });
+    }
+
+    (cmd, found)
+}
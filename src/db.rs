@@ -2,21 +2,48 @@
 // This file is dual-licensed as Apache-2.0 or GPL-3.0.
 // see LICENSE for license details.
 
+use crate::events::{Event, EVENTS_CAPACITY};
 use crate::types::ElectionResult as InnerElectionResult;
-use crate::{Address, LOG_TARGET};
+use crate::types::{format_tokens, ElectionRound, FailureReason, Hash};
+use crate::{ingestion, Address, LOG_TARGET};
+use deadpool_postgres::{Manager, ManagerConfig, Object, Pool, PoolError, RecyclingMethod};
+use futures::future::BoxFuture;
 use oasgen::OaSchema;
 use polkadot_sdk::sp_npos_elections::ElectionScore;
+use refinery::Migration;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::num::NonZeroUsize;
+use std::collections::HashMap;
+use std::error::Error as _;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 use tokio_postgres::row::Row;
+use tokio_postgres::types::{Json, ToSql};
 use tokio_postgres::{Client, NoTls};
 use url::Url;
 
 refinery::embed_migrations!("migrations");
 
+#[cfg(test)]
+pub mod memory;
+
+/// All tables, each of them has a `round` column.
+pub const TABLES: &[&str] = &[
+    "submissions",
+    "elections",
+    "slashed",
+    "ejections",
+    "phase_transitions",
+    "config_changes",
+];
+
+/// The tables written while reading a block, see [`Database::delete_block`].
+const BLOCK_TABLES: &[&str] = &["submissions", "slashed", "ejections", "phase_transitions"];
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Failed to decode/encode: {0}")]
@@ -26,229 +53,2362 @@ pub enum Error {
     #[error(transparent)]
     Database(#[from] tokio_postgres::Error),
     #[error(transparent)]
+    Pool(#[from] PoolError),
+    #[error(transparent)]
     Migration(#[from] refinery::Error),
+    #[error("The migrations {0} were changed after they were applied to the database, restore them or pass --allow-migration-drift to run anyway")]
+    MigrationDrift(String),
+}
+
+impl Error {
+    /// Whether retrying may succeed, e.g. after the connection was lost or the server was restarted.
+    fn is_transient(&self) -> bool {
+        let e = match self {
+            Self::Database(e) | Self::Pool(PoolError::Backend(e)) => e,
+            Self::Pool(PoolError::Timeout(_)) => return true,
+            _ => return false,
+        };
+        e.is_closed()
+            || e.source().is_some_and(|e| e.is::<std::io::Error>())
+            || e.code().is_some_and(|code| is_transient_code(code.code()))
+    }
+}
+
+/// Whether the SQLSTATE `code` is of a connection exception, serialization failure, deadlock,
+/// insufficient resources or the server shutting down or starting up.
+fn is_transient_code(code: &str) -> bool {
+    ["08", "40001", "40P01", "53", "57P01", "57P02", "57P03"]
+        .iter()
+        .any(|prefix| code.starts_with(prefix))
+}
+
+/// Run `f` until it succeeds, fails with an error that isn't transient or was run `attempts` times.
+///
+/// The first retry waits `delay`, which is doubled for each further one.
+async fn retry<T, F, Fut>(attempts: NonZeroU32, mut delay: Duration, mut f: F) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, Error>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match f().await {
+            Err(e) if attempt < attempts.get() && e.is_transient() => {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "Database write failed (attempt {attempt}/{attempts}), retrying in {delay:?}: {e}"
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// The migrations that haven't been applied yet and the applied ones whose files were changed since.
+pub struct MigrationStatus {
+    pub pending: Vec<String>,
+    pub drifted: Vec<String>,
+}
+
+/// The applied migrations whose checksum doesn't match the embedded migration anymore.
+fn drifted_migrations(applied: &[Migration], embedded: &[Migration]) -> Vec<String> {
+    embedded
+        .iter()
+        .filter(|m| {
+            applied
+                .iter()
+                .any(|a| a.version() == m.version() && a.checksum() != m.checksum())
+        })
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Get the migrations applied to the database, none if no migration was ever applied.
+async fn applied_migrations(
+    runner: &refinery::Runner,
+    db: &mut Client,
+) -> Result<Vec<Migration>, Error> {
+    let row = db
+        .query_one(
+            "SELECT to_regclass('refinery_schema_history') IS NOT NULL",
+            &[],
+        )
+        .await?;
+    if !row.get::<_, bool>(0) {
+        return Ok(Vec::new());
+    }
+
+    Ok(runner.get_applied_migrations_async(db).await?)
+}
+
+/// Compare the migrations of the database with the embedded ones, without applying them.
+pub async fn migration_status(url: Url) -> Result<MigrationStatus, Error> {
+    let (mut db, _) = connect(&url).await?;
+
+    let runner = migrations::runner();
+    let applied = applied_migrations(&runner, &mut db).await?;
+
+    let pending = runner
+        .get_migrations()
+        .iter()
+        .filter(|m| !applied.iter().any(|a| a.version() == m.version()))
+        .map(ToString::to_string)
+        .collect();
+
+    Ok(MigrationStatus {
+        pending,
+        drifted: drifted_migrations(&applied, runner.get_migrations()),
+    })
+}
+
+/// Connect to the database, the returned task drives the connection until the client is dropped.
+async fn connect(url: &Url) -> Result<(Client, JoinHandle<()>), Error> {
+    let (db, connection) = tokio_postgres::connect(url.as_str(), NoTls).await?;
+
+    let task = tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::error!(target: LOG_TARGET, "connection error: {e}");
+        }
+    });
+
+    Ok((db, task))
+}
+
+/// A pool of at most `size` connections, which are opened when needed and replaced once closed,
+/// e.g. because the database was restarted.
+fn pool(url: &Url, size: NonZeroUsize) -> Result<Pool, Error> {
+    let config = tokio_postgres::Config::from_str(url.as_str())?;
+    let manager = Manager::from_config(
+        config,
+        NoTls,
+        ManagerConfig {
+            recycling_method: RecyclingMethod::Fast,
+        },
+    );
+
+    Pool::builder(manager)
+        .max_size(size.get())
+        .build()
+        .map_err(|e| Error::Parse(e.to_string()))
+}
+
+/// Only the rows whose block timestamp is in `from..=to`, either bound is optional.
+#[derive(Debug, Default, Clone, Deserialize, OaSchema)]
+pub struct TimeRange {
+    /// The earliest block timestamp in RFC 3339 format, e.g. `2024-09-01T00:00:00Z`.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    /// The latest block timestamp in RFC 3339 format.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+}
+
+/// The conditions of a `WHERE` clause and the values of their placeholders.
+#[derive(Default)]
+struct Filter<'a> {
+    conditions: Vec<String>,
+    params: Vec<&'a (dyn ToSql + Sync)>,
+}
+
+impl<'a> Filter<'a> {
+    /// Add a condition, the `$` in it is the placeholder of `value`.
+    fn and(&mut self, condition: &str, value: &'a (dyn ToSql + Sync)) {
+        self.params.push(value);
+        self.conditions
+            .push(condition.replace('$', &format!("${}", self.params.len())));
+    }
+
+    fn spec_version(&mut self, spec_version: &'a Option<u32>) {
+        if let Some(spec_version) = spec_version {
+            self.and("spec_version = $", spec_version);
+        }
+    }
+
+    fn time_range(&mut self, range: &'a TimeRange) {
+        if let Some(from) = &range.from {
+            self.and("created_at >= $", from);
+        }
+        if let Some(to) = &range.to {
+            self.and("created_at <= $", to);
+        }
+    }
+
+    /// The `FROM` clause of the filtered `table`.
+    fn from(&self, table: &str) -> String {
+        if self.conditions.is_empty() {
+            table.to_string()
+        } else {
+            format!("{table} WHERE {}", self.conditions.join(" AND "))
+        }
+    }
+}
+
+/// The column to sort by, the SQL of it is a fixed column name rather than user input.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SortColumn {
+    #[default]
+    Round,
+    Block,
+}
+
+impl FromStr for SortColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round" => Ok(Self::Round),
+            "block" => Ok(Self::Block),
+            _ => Err(format!("Invalid sort `{s}`, expected `round` or `block`")),
+        }
+    }
+}
+
+/// The direction to sort in, the SQL of it is a fixed keyword rather than user input.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(Self::Asc),
+            "desc" => Ok(Self::Desc),
+            _ => Err(format!("Invalid order `{s}`, expected `asc` or `desc`")),
+        }
+    }
+}
+
+/// The result of the elections to get, the SQL of it is a fixed value rather than user input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ElectionResultKind {
+    Signed,
+    Unsigned,
+    Failed,
+    GovernanceFallback,
+}
+
+impl ElectionResultKind {
+    /// The value of the `result` column.
+    fn as_sql(self) -> &'static str {
+        match self {
+            Self::Signed => "signed",
+            Self::Unsigned => "unsigned",
+            Self::Failed => "election failed",
+            Self::GovernanceFallback => "governance fallback",
+        }
+    }
+}
+
+impl FromStr for ElectionResultKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "signed" => Ok(Self::Signed),
+            "unsigned" => Ok(Self::Unsigned),
+            "failed" => Ok(Self::Failed),
+            "governance_fallback" => Ok(Self::GovernanceFallback),
+            _ => Err(format!(
+                "Invalid result `{s}`, expected `signed`, `unsigned`, `failed` or `governance_fallback`"
+            )),
+        }
+    }
+}
+
+/// How to sort the most recent rows, by `round` descending by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Sort {
+    pub column: SortColumn,
+    pub order: SortOrder,
+}
+
+impl Sort {
+    /// The query of the `$1` most recent rows of `table` by the sort column, sorted as requested.
+    fn most_recent(&self, table: &str) -> String {
+        let column = match self.column {
+            SortColumn::Round => "round",
+            SortColumn::Block => "block",
+        };
+        let order = match self.order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+
+        format!("SELECT * FROM (SELECT * FROM {table} ORDER BY {column} DESC LIMIT $1) recent ORDER BY {column} {order}")
+    }
+}
+
+/// A page of a list and the number of items across all pages.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub total: u64,
+    pub items: Vec<T>,
+}
+
+// The derive macro doesn't support generic structs.
+impl<T: OaSchema> OaSchema for Page<T> {
+    fn schema() -> oasgen::Schema {
+        let mut schema = oasgen::Schema::new_object();
+        schema.properties_mut().insert("total", u64::schema());
+        schema.properties_mut().insert("items", Vec::<T>::schema());
+        schema
+            .required_mut()
+            .extend(["total".to_string(), "items".to_string()]);
+        schema
+    }
 }
 
+/// The first delay before retrying a failed insert, doubled after each attempt.
+const INSERT_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// The number of attempts of an insert unless set with [`Database::with_insert_attempts`].
+pub const DEFAULT_INSERT_ATTEMPTS: NonZeroU32 = NonZeroU32::new(5).expect("5 is non-zero; qed");
+
 #[derive(Debug, Clone)]
-pub struct Database(Arc<Client>);
+pub struct Database {
+    pool: Pool,
+    /// The broadcast of inserted rows.
+    events: broadcast::Sender<Event>,
+    /// Whether writes are skipped, with `--dry-run`.
+    read_only: bool,
+    /// The number of attempts of a write that fails with a transient error.
+    write_retries: NonZeroU32,
+}
 
 impl Database {
-    pub async fn new(url: Url) -> Result<Self, Error> {
+    /// Connect to the database with a pool of `pool_size` connections and apply the pending migrations.
+    ///
+    /// Fails if an applied migration was changed since, unless `allow_migration_drift` is set.
+    pub async fn new(
+        url: Url,
+        pool_size: NonZeroUsize,
+        allow_migration_drift: bool,
+    ) -> Result<Self, Error> {
         tracing::debug!(target: LOG_TARGET, "connecting to postgres db: {url}");
-        let (mut db, connection) = tokio_postgres::connect(url.as_str(), NoTls).await?;
+        let (mut db, task) = connect(&url).await?;
+
+        let mut runner = migrations::runner();
+        let applied = applied_migrations(&runner, &mut db).await?;
+        let drifted = drifted_migrations(&applied, runner.get_migrations());
+
+        if !drifted.is_empty() {
+            if !allow_migration_drift {
+                return Err(Error::MigrationDrift(drifted.join(", ")));
+            }
+            tracing::warn!(target: LOG_TARGET, "The migrations {} were changed after they were applied, ignoring", drifted.join(", "));
+            runner = runner.set_abort_divergent(false);
+        }
+
+        runner.run_async(&mut db).await?;
+        drop(db);
+        _ = task.await;
 
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                tracing::error!(target: LOG_TARGET, "connection error: {e}");
+        let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+        Ok(Self {
+            pool: pool(&url, pool_size)?,
+            events,
+            read_only: false,
+            write_retries: DEFAULT_INSERT_ATTEMPTS,
+        })
+    }
+
+    /// Close the connections to the database, waiting up to `timeout` for the queries
+    /// that are still running.
+    ///
+    /// Returns whether all connections were closed in time. Queries after this fail.
+    pub async fn close(&self, timeout: Duration) -> bool {
+        self.pool.close();
+        // The connections in use are closed once they're returned to the pool.
+        let closed = async {
+            while self.pool.status().size > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
             }
-        });
+        };
+        tokio::time::timeout(timeout, closed).await.is_ok()
+    }
+
+    /// Log the rows instead of inserting them and skip all other writes, queries still work.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.read_only = dry_run;
+        self
+    }
 
-        migrations::runner().run_async(&mut db).await?;
-        Ok(Self(Arc::new(db)))
+    /// Retry inserts that failed with a transient error, such as a lost connection,
+    /// until `attempts` attempts failed.
+    pub fn with_insert_attempts(mut self, attempts: NonZeroU32) -> Self {
+        self.write_retries = attempts;
+        self
+    }
+
+    /// Run a statement that writes to the database, retrying transient errors with an exponential backoff.
+    ///
+    /// The statement is prepared again on each attempt since the connection may have been replaced.
+    async fn write(&self, statement: &str, params: &[&(dyn ToSql + Sync)]) -> Result<(), Error> {
+        retry(self.write_retries, INSERT_RETRY_DELAY, || async {
+            let client = self.client().await?;
+            let stmt = client.prepare(statement).await?;
+            client.execute(&stmt, params).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Whether writes are skipped, the row that would have been written is logged.
+    fn dry_run(&self, table: &str, row: &dyn std::fmt::Debug) -> bool {
+        if self.read_only {
+            tracing::info!(target: LOG_TARGET, "Dry run, not writing to {table}: {row:?}");
+        }
+        self.read_only
+    }
+
+    /// Get a connection from the pool, reconnecting if it was closed.
+    async fn client(&self) -> Result<Object, Error> {
+        Ok(self.pool.get().await?)
+    }
+
+    /// Check that the database is reachable.
+    pub async fn ping(&self) -> Result<(), Error> {
+        self.client().await?.execute("SELECT 1", &[]).await?;
+        Ok(())
+    }
+
+    /// Subscribe to the rows inserted into the database from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Broadcast an inserted row, it's fine if there are no subscribers.
+    fn broadcast(&self, event: Event) {
+        _ = self.events.send(event);
     }
 
     pub async fn insert_submission(&self, submission: Submission) -> Result<(), Error> {
+        if self.dry_run("submissions", &submission) {
+            self.broadcast(Event::Submission(submission));
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let event = submission.clone();
         let Submission {
             who,
             round,
             block,
             score,
             success,
+            extrinsic_hash,
+            stale_snapshot,
+            extrinsic_index,
+            failure_reason,
+            created_at,
+            blocks_to_finalize: _,
+            spec_version,
+            solution_voters,
+            solution_winners,
+            prev_round_result: _,
         } = submission;
 
         let who = who.to_string();
-        let stmt = self.0.prepare("INSERT INTO submissions (address, round, block, score, success) VALUES ($1, $2, $3, $4, $5)").await?;
-        self.0
-            .execute(&stmt, &[&who, &round, &block, &score, &success])
-            .await?;
+        self.write(
+            "INSERT INTO submissions (address, round, block, score, success, extrinsic_hash, stale_snapshot, extrinsic_index, failure_reason, created_at, spec_version, solution_voters, solution_winners) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+            &[
+                &who,
+                &round,
+                &block,
+                &Json(&score),
+                &success,
+                &extrinsic_hash,
+                &stale_snapshot,
+                &extrinsic_index,
+                &failure_reason,
+                &created_at,
+                &spec_version,
+                &solution_voters,
+                &solution_winners,
+            ],
+        )
+        .await?;
 
+        self.broadcast(Event::Submission(event));
+        ingestion::record_db_insert(now.elapsed());
         Ok(())
     }
 
     pub async fn insert_election(&self, election: Election) -> Result<(), Error> {
+        if self.dry_run("elections", &election) {
+            self.broadcast(Event::Election(election));
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let event = election.clone();
         let Election {
             result,
             winner,
             round,
             block,
             score,
-            ..
+            block_author,
+            met_target,
+            phase_durations,
+            snapshot_voters,
+            snapshot_targets,
+            created_at,
+            winners,
+            spec_version,
         } = election;
 
-        let stmt = self
-            .0
-            .prepare(
-                "INSERT INTO elections (result, address, round, block, score) VALUES ($1, $2, $3, $4, $5)",
+        let block_author = block_author.map(|a| a.to_string());
+
+        self.write(
+            "INSERT INTO elections (result, address, round, block, score, block_author, met_target, phase_durations, snapshot_voters, snapshot_targets, created_at, winners, spec_version) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+            &[
+                &result,
+                &addresses_to_json(winner.as_deref()),
+                &round,
+                &block,
+                &Json(&score),
+                &block_author,
+                &met_target,
+                &phase_durations.map(Json),
+                &snapshot_voters,
+                &snapshot_targets,
+                &created_at,
+                &addresses_to_json(winners.as_deref()),
+                &spec_version,
+            ],
+        )
+        .await?;
+
+        if result != "election failed" {
+            self.write(
+                "UPDATE submissions SET blocks_to_finalize = ($1 - block::BIGINT)::OID WHERE round = $2 AND block <= $3",
+                &[&(block as i64), &round, &block],
             )
             .await?;
-        self.0
-            .execute(&stmt, &[&result, &winner, &round, &block, &score])
-            .await?;
+        }
 
+        self.broadcast(Event::Election(event));
+        ingestion::record_db_insert(now.elapsed());
         Ok(())
     }
 
     pub async fn insert_slashed(&self, slashed: Slashed) -> Result<(), Error> {
+        if self.dry_run("slashed", &slashed) {
+            self.broadcast(Event::Slashed(slashed));
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let event = slashed.clone();
         let Slashed {
             who,
             round,
             block,
-            amount,
+            amount_planck,
+            amount_tokens,
+            created_at,
         } = slashed;
 
         let who = who.to_string();
 
-        let stmt = self
-            .0
-            .prepare("INSERT INTO slashed (address, amount, round, block) VALUES ($1, $2, $3, $4)")
-            .await?;
-        self.0
-            .execute(&stmt, &[&who, &amount, &round, &block])
-            .await?;
+        self.write(
+            "INSERT INTO slashed (address, amount_planck, round, block, created_at, amount_tokens) VALUES ($1, $2, $3, $4, $5, $6)",
+            &[&who, &amount_planck, &round, &block, &created_at, &amount_tokens],
+        )
+        .await?;
 
+        self.broadcast(Event::Slashed(event));
+        ingestion::record_db_insert(now.elapsed());
         Ok(())
     }
 
-    pub async fn get_all_submissions(&self) -> Result<Vec<Submission>, Error> {
-        collect_db_rows(self.0.query("SELECT * FROM submissions", &[]).await?)
+    pub async fn insert_ejection(&self, ejection: Ejection) -> Result<(), Error> {
+        if self.dry_run("ejections", &ejection) {
+            self.broadcast(Event::Ejection(ejection));
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let event = ejection.clone();
+        let Ejection {
+            who,
+            ejected_by,
+            round,
+            block,
+        } = ejection;
+
+        let who = who.to_string();
+        let ejected_by = ejected_by.to_string();
+
+        self.write(
+            "INSERT INTO ejections (address, ejected_by, round, block) VALUES ($1, $2, $3, $4)",
+            &[&who, &ejected_by, &round, &block],
+        )
+        .await?;
+
+        self.broadcast(Event::Ejection(event));
+        ingestion::record_db_insert(now.elapsed());
+        Ok(())
     }
 
-    pub async fn get_all_success_submissions(&self) -> Result<Vec<Submission>, Error> {
-        collect_db_rows(
-            self.0
-                .query("SELECT * FROM submissions where success = true", &[])
-                .await?,
+    pub async fn insert_phase_transition(&self, transition: PhaseTransition) -> Result<(), Error> {
+        if self.dry_run("phase_transitions", &transition) {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let PhaseTransition {
+            round,
+            block,
+            from,
+            to,
+        } = transition;
+
+        self.write(
+            "INSERT INTO phase_transitions (round, block, from_phase, to_phase) VALUES ($1, $2, $3, $4)",
+            &[&round, &block, &from, &to],
+        )
+        .await?;
+
+        ingestion::record_db_insert(now.elapsed());
+        Ok(())
+    }
+
+    pub async fn insert_config_change(&self, change: ConfigChange) -> Result<(), Error> {
+        if self.dry_run("config_changes", &change) {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let ConfigChange {
+            call,
+            signer,
+            round,
+            block,
+            args,
+        } = change;
+        let signer = signer.map(|s| s.to_string());
+
+        self.write(
+            "INSERT INTO config_changes (call, signer, round, block, args) VALUES ($1, $2, $3, $4, $5)",
+            &[&call, &signer, &round, &block, &args],
         )
+        .await?;
+
+        ingestion::record_db_insert(now.elapsed());
+        Ok(())
     }
 
-    pub async fn get_all_failed_submissions(&self) -> Result<Vec<Submission>, Error> {
+    pub async fn get_all_submissions(&self) -> Result<Vec<Submission>, Error> {
         collect_db_rows(
-            self.0
-                .query("SELECT * FROM submissions where success = false", &[])
+            self.client()
+                .await?
+                .query("SELECT * FROM submissions", &[])
                 .await?,
         )
     }
 
-    pub async fn get_all_unsigned_elections(&self) -> Result<Vec<Election>, Error> {
+    /// Get all submissions by any of the given addresses, each with the result
+    /// of the same address in the previous round.
+    pub async fn get_submissions_by_addresses(
+        &self,
+        addresses: &[Address],
+    ) -> Result<Vec<Submission>, Error> {
+        let addresses: Vec<String> = addresses.iter().map(ToString::to_string).collect();
         collect_db_rows(
-            self.0
-                .query("SELECT * FROM elections where result = 'unsigned'", &[])
+            self.client()
+                .await?
+                .query(
+                    &format!(
+                        "SELECT s.*, {} AS prev_round_result
+                        FROM submissions s WHERE s.address = ANY($1)",
+                        round_result("s.address", "s.round::BIGINT - 1")
+                    ),
+                    &[&addresses],
+                )
                 .await?,
         )
     }
 
-    pub async fn get_all_signed_elections(&self) -> Result<Vec<Election>, Error> {
+    /// Get the submission by the hash of its extrinsic.
+    pub async fn get_submission_by_extrinsic_hash(
+        &self,
+        hash: Hash,
+    ) -> Result<Option<Submission>, Error> {
+        let hash = format!("{hash:?}");
+        self.client()
+            .await?
+            .query_opt(
+                "SELECT * FROM submissions WHERE extrinsic_hash = $1 LIMIT 1",
+                &[&hash],
+            )
+            .await?
+            .map(Submission::try_from)
+            .transpose()
+    }
+
+    pub async fn get_all_elections(&self) -> Result<Vec<Election>, Error> {
         collect_db_rows(
-            self.0
-                .query("SELECT * FROM elections where result = 'signed'", &[])
+            self.client()
+                .await?
+                .query("SELECT * FROM elections", &[])
                 .await?,
         )
     }
 
-    pub async fn get_all_failed_elections(&self) -> Result<Vec<Election>, Error> {
+    pub async fn get_all_slashed(&self) -> Result<Vec<Slashed>, Error> {
         collect_db_rows(
-            self.0
-                .query(
-                    "SELECT * FROM elections where result = 'election failed'",
-                    &[],
-                )
+            self.client()
+                .await?
+                .query("SELECT * FROM slashed", &[])
                 .await?,
         )
     }
 
-    pub async fn get_all_elections(&self) -> Result<Vec<Election>, Error> {
-        collect_db_rows(self.0.query("SELECT * FROM elections", &[]).await?)
+    /// Get the submissions, only those of `address`, in the time range and of the spec version if given.
+    pub async fn get_submissions_paginated(
+        &self,
+        address: Option<&Address>,
+        time_range: &TimeRange,
+        spec_version: Option<u32>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<Submission>, Error> {
+        let address = address.map(ToString::to_string);
+        let mut filter = Filter::default();
+
+        if let Some(address) = &address {
+            filter.and("address = $", address);
+        }
+        filter.time_range(time_range);
+        filter.spec_version(&spec_version);
+
+        self.get_page(
+            &filter.from("submissions"),
+            "id",
+            &filter.params,
+            limit,
+            offset,
+        )
+        .await
     }
 
-    pub async fn get_all_slashed(&self) -> Result<Vec<Slashed>, Error> {
-        collect_db_rows(self.0.query("SELECT * FROM slashed", &[]).await?)
+    /// Get the submissions whose solution has a minimal stake below `threshold` plancks, the weakest first.
+    pub async fn get_submissions_by_min_stake(
+        &self,
+        threshold: u128,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<Submission>, Error> {
+        let threshold = threshold.to_string();
+        self.get_page(
+            "submissions WHERE (score->>'minimal_stake')::NUMERIC < $1::TEXT::NUMERIC",
+            "(score->>'minimal_stake')::NUMERIC, id",
+            &[&threshold],
+            limit,
+            offset,
+        )
+        .await
     }
 
-    pub async fn get_most_recent_submissions(
+    pub async fn get_success_submissions_paginated(
         &self,
-        n: NonZeroUsize,
-    ) -> Result<Vec<Submission>, Error> {
-        collect_db_rows(
-            self.0
-                .query(
-                    &format!("SELECT * FROM submissions ORDER BY round DESC LIMIT {n}"),
-                    &[],
-                )
-                .await?,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<Submission>, Error> {
+        self.get_page("submissions WHERE success = true", "id", &[], limit, offset)
+            .await
+    }
+
+    pub async fn get_failed_submissions_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<Submission>, Error> {
+        self.get_page(
+            "submissions WHERE success = false",
+            "id",
+            &[],
+            limit,
+            offset,
+        )
+        .await
+    }
+
+    /// Get the elections, only those in the time range, of the spec version and with the result if given.
+    pub async fn get_elections_paginated(
+        &self,
+        time_range: &TimeRange,
+        spec_version: Option<u32>,
+        result: Option<ElectionResultKind>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<Election>, Error> {
+        let result = result.map(ElectionResultKind::as_sql);
+        let mut filter = Filter::default();
+        filter.time_range(time_range);
+        filter.spec_version(&spec_version);
+        if let Some(result) = &result {
+            filter.and("result = $", result);
+        }
+
+        self.get_page(
+            &filter.from("elections"),
+            "id",
+            &filter.params,
+            limit,
+            offset,
+        )
+        .await
+    }
+
+    /// Get the elections that were finalized by an unsigned solution
+    /// even though signed solutions were submitted in the same round.
+    pub async fn get_unsigned_despite_signed_elections_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<Election>, Error> {
+        self.get_page(
+            "elections e WHERE e.result = 'unsigned' AND EXISTS (
+                SELECT 1 FROM submissions s WHERE s.round = e.round AND s.address != 'unsigned'
+            )",
+            "e.id",
+            &[],
+            limit,
+            offset,
+        )
+        .await
+    }
+
+    /// Get the slashes, only those of `address`, of at least `min_amount` and in the time range if given.
+    ///
+    /// With `min_amount`, the largest slashes come first.
+    pub async fn get_slashed_paginated(
+        &self,
+        address: Option<&Address>,
+        min_amount: Option<u128>,
+        time_range: &TimeRange,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<Slashed>, Error> {
+        let address = address.map(ToString::to_string);
+        let min_amount = min_amount.map(|a| a.to_string());
+        let mut filter = Filter::default();
+
+        if let Some(address) = &address {
+            filter.and("address = $", address);
+        }
+        if let Some(min_amount) = &min_amount {
+            // The amount is a u128 stored as text, which doesn't fit into any integer type.
+            filter.and("amount_planck::NUMERIC >= $::TEXT::NUMERIC", min_amount);
+        }
+        filter.time_range(time_range);
+
+        let order = match min_amount {
+            Some(_) => "amount_planck::NUMERIC DESC",
+            None => "id",
+        };
+
+        self.get_page(
+            &filter.from("slashed"),
+            order,
+            &filter.params,
+            limit,
+            offset,
         )
+        .await
+    }
+
+    pub async fn get_ejections_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<Ejection>, Error> {
+        self.get_page("ejections", "id", &[], limit, offset).await
+    }
+
+    pub async fn get_config_changes_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<ConfigChange>, Error> {
+        self.get_page("config_changes", "id", &[], limit, offset)
+            .await
+    }
+
+    /// Get `limit` rows of `SELECT * FROM {from} ORDER BY {order}` after skipping `offset` rows,
+    /// together with the number of all rows.
+    ///
+    /// `params` are the values of the placeholders in `from`, which must not contain user input itself.
+    async fn get_page<T>(
+        &self,
+        from: &str,
+        order: &str,
+        params: &[&(dyn ToSql + Sync)],
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<T>, Error>
+    where
+        T: TryFrom<Row, Error = Error>,
+    {
+        let (total, rows) = self
+            .query_page(
+                &format!("SELECT * FROM {from} ORDER BY {order}"),
+                params,
+                limit,
+                offset,
+            )
+            .await?;
+        Ok(Page {
+            total,
+            items: collect_db_rows(rows)?,
+        })
+    }
+
+    /// Get `limit` rows of the ordered `query` after skipping `offset` rows,
+    /// together with the number of all rows.
+    async fn query_page(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+        limit: u32,
+        offset: u32,
+    ) -> Result<(u64, Vec<Row>), Error> {
+        let client = self.client().await?;
+        let total = client
+            .query_one(&format!("SELECT COUNT(*) FROM ({query}) AS q"), params)
+            .await?
+            .get::<_, i64>(0) as u64;
+
+        let (limit, offset) = (limit as i64, offset as i64);
+        let mut page_params = params.to_vec();
+        page_params.push(&limit);
+        page_params.push(&offset);
+
+        let rows = client
+            .query(
+                &format!(
+                    "{query} LIMIT ${} OFFSET ${}",
+                    params.len() + 1,
+                    params.len() + 2
+                ),
+                &page_params,
+            )
+            .await?;
+
+        Ok((total, rows))
+    }
+
+    /// Get up to `limit` rows of `from` ordered by id together with their ids.
+    ///
+    /// Unlike [`Self::get_page`] this doesn't count the rows, it's used to read large tables in chunks.
+    async fn get_chunk<T>(
+        &self,
+        from: &str,
+        params: &[&(dyn ToSql + Sync)],
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<(i32, T)>, Error>
+    where
+        T: TryFrom<Row, Error = Error>,
+    {
+        let (limit, offset) = (limit as i64, offset as i64);
+        let mut chunk_params = params.to_vec();
+        chunk_params.push(&limit);
+        chunk_params.push(&offset);
+
+        let rows = self
+            .client()
+            .await?
+            .query(
+                &format!(
+                    "SELECT * FROM {from} ORDER BY id LIMIT ${} OFFSET ${}",
+                    params.len() + 1,
+                    params.len() + 2
+                ),
+                &chunk_params,
+            )
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let id = row.try_get(0).map_err(|_| Error::RowNotFound("id", 0))?;
+                Ok((id, T::try_from(row)?))
+            })
+            .collect()
+    }
+
+    /// Get up to `limit` submissions with an id above `after`, filtered and ordered as
+    /// [`Self::get_submissions_paginated`], together with their ids.
+    pub async fn get_submissions_after(
+        &self,
+        address: Option<&Address>,
+        time_range: &TimeRange,
+        spec_version: Option<u32>,
+        after: i32,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<(i32, Submission)>, Error> {
+        let address = address.map(ToString::to_string);
+        let mut filter = Filter::default();
+
+        if let Some(address) = &address {
+            filter.and("address = $", address);
+        }
+        filter.time_range(time_range);
+        filter.spec_version(&spec_version);
+        filter.and("id > $", &after);
+
+        self.get_chunk(&filter.from("submissions"), &filter.params, limit, offset)
+            .await
+    }
+
+    /// Get up to `limit` elections with an id above `after`, filtered and ordered as
+    /// [`Self::get_elections_paginated`], together with their ids.
+    pub async fn get_elections_after(
+        &self,
+        time_range: &TimeRange,
+        spec_version: Option<u32>,
+        after: i32,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<(i32, Election)>, Error> {
+        let mut filter = Filter::default();
+        filter.time_range(time_range);
+        filter.spec_version(&spec_version);
+        filter.and("id > $", &after);
+
+        self.get_chunk(&filter.from("elections"), &filter.params, limit, offset)
+            .await
+    }
+
+    pub async fn get_all_ejections(&self) -> Result<Vec<Ejection>, Error> {
+        collect_db_rows(
+            self.client()
+                .await?
+                .query("SELECT * FROM ejections", &[])
+                .await?,
+        )
+    }
+
+    /// Get the `n` most recent submissions by the column of `sort`, in its order.
+    pub async fn get_most_recent_submissions(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> Result<Vec<Submission>, Error> {
+        collect_db_rows(
+            self.client()
+                .await?
+                .query(&sort.most_recent("submissions"), &[&(n.get() as i64)])
+                .await?,
+        )
+    }
+
+    /// Get the `n` most recent elections by the column of `sort`, in its order.
+    pub async fn get_most_recent_elections(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> Result<Vec<Election>, Error> {
+        collect_db_rows(
+            self.client()
+                .await?
+                .query(&sort.most_recent("elections"), &[&(n.get() as i64)])
+                .await?,
+        )
+    }
+
+    /// Get the most recent elections after skipping `offset` of them, each with the submissions of its round.
+    pub async fn get_elections_with_submissions(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<ElectionWithSubmissions>, Error> {
+        let Page {
+            total,
+            items: elections,
+        } = self
+            .get_page::<Election>("elections", "round DESC", &[], limit, offset)
+            .await?;
+
+        let rounds: Vec<u32> = elections.iter().map(|e| e.round).collect();
+        let submissions: Vec<Submission> = collect_db_rows(
+            self.client().await?
+                .query(
+                    "SELECT * FROM submissions WHERE round = ANY($1) ORDER BY block, extrinsic_index",
+                    &[&rounds],
+                )
+                .await?,
+        )?;
+
+        let mut by_round: HashMap<u32, Vec<Submission>> = HashMap::new();
+        for submission in submissions {
+            by_round
+                .entry(submission.round)
+                .or_default()
+                .push(submission);
+        }
+
+        let items = elections
+            .into_iter()
+            .map(|election| ElectionWithSubmissions {
+                submissions: by_round.remove(&election.round).unwrap_or_default(),
+                election,
+            })
+            .collect();
+        Ok(Page { total, items })
+    }
+
+    /// Get the submissions of `round` in the order they were processed in.
+    pub async fn get_submissions_for_round(&self, round: u32) -> Result<Vec<Submission>, Error> {
+        collect_db_rows(
+            self.client()
+                .await?
+                .query(
+                    "SELECT * FROM submissions WHERE round = $1 ORDER BY block, extrinsic_index",
+                    &[&round],
+                )
+                .await?,
+        )
+    }
+
+    /// Get the election of `round`, `None` if it wasn't recorded.
+    pub async fn get_election_for_round(&self, round: u32) -> Result<Option<Election>, Error> {
+        self.client()
+            .await?
+            .query_opt(
+                "SELECT * FROM elections WHERE round = $1 ORDER BY id DESC LIMIT 1",
+                &[&round],
+            )
+            .await?
+            .map(Election::try_from)
+            .transpose()
+    }
+
+    /// Get the slashes of `round`.
+    pub async fn get_slashed_for_round(&self, round: u32) -> Result<Vec<Slashed>, Error> {
+        collect_db_rows(
+            self.client()
+                .await?
+                .query(
+                    "SELECT * FROM slashed WHERE round = $1 ORDER BY id",
+                    &[&round],
+                )
+                .await?,
+        )
+    }
+
+    /// Get the election, submissions and slashes of `round`, `None` if nothing was recorded in it.
+    pub async fn get_round_activity(&self, round: u32) -> Result<Option<RoundActivity>, Error> {
+        let activity = RoundActivity {
+            election: self.get_election_for_round(round).await?,
+            submissions: self.get_submissions_for_round(round).await?,
+            slashed: self.get_slashed_for_round(round).await?,
+        };
+
+        if activity.election.is_none()
+            && activity.submissions.is_empty()
+            && activity.slashed.is_empty()
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(activity))
+    }
+
+    /// Get the `n` most recent slashes by the column of `sort`, in its order.
+    pub async fn get_most_recent_slashed(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> Result<Vec<Slashed>, Error> {
+        collect_db_rows(
+            self.client()
+                .await?
+                .query(&sort.most_recent("slashed"), &[&(n.get() as i64)])
+                .await?,
+        )
+    }
+
+    pub async fn get_stats(&self) -> Result<Stats, Error> {
+        let submissions = self
+            .collect_count("SELECT COUNT(*) FROM submissions")
+            .await?;
+
+        let submissions_failed = self
+            .collect_count("SELECT COUNT(*) FROM submissions WHERE success = false")
+            .await?;
+
+        let submissions_success = self
+            .collect_count("SELECT COUNT(*) FROM submissions WHERE success = true")
+            .await?;
+
+        let failure_reasons = self.client().await?
+            .query(
+                "SELECT failure_reason, COUNT(*) FROM submissions WHERE failure_reason IS NOT NULL GROUP BY failure_reason",
+                &[],
+            )
+            .await?
+            .into_iter()
+            .map(|row| {
+                let reason: String = row
+                    .try_get(0)
+                    .map_err(|_| Error::RowNotFound("failure_reason", 0))?;
+                let count: i64 = row.try_get(1).map_err(|_| Error::RowNotFound("count", 1))?;
+                Ok((reason, count as u64))
+            })
+            .collect::<Result<HashMap<_, _>, Error>>()?;
+
+        let elections = self.collect_count("SELECT COUNT(*) FROM elections").await?;
+
+        let elections_failed = self
+            .collect_count("SELECT COUNT(*) FROM elections WHERE result = 'election failed'")
+            .await?;
+
+        let elections_signed = self
+            .collect_count("SELECT COUNT(*) FROM elections WHERE result = 'signed'")
+            .await?;
+
+        let elections_unsigned = self
+            .collect_count("SELECT COUNT(*) FROM elections WHERE result = 'unsigned'")
+            .await?;
+
+        let elections_governance_fallback = self
+            .collect_count("SELECT COUNT(*) FROM elections WHERE result = 'governance fallback'")
+            .await?;
+
+        let slashed = self.collect_count("SELECT COUNT(*) FROM slashed").await?;
+
+        Ok(Stats {
+            submissions: Submissions {
+                total: submissions,
+                failed: submissions_failed,
+                success: submissions_success,
+                failure_reasons,
+            },
+            elections: Elections {
+                total: elections,
+                failed: elections_failed,
+                signed: elections_signed,
+                unsigned: elections_unsigned,
+                governance_fallback: elections_governance_fallback,
+            },
+            slashed,
+        })
+    }
+
+    /// Get the signed miners that never had a successful submission.
+    pub async fn get_never_successful_miners(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<NeverSuccessfulMiner>, Error> {
+        let (total, rows) = self
+            .query_page(
+                "SELECT address, COUNT(*) AS submissions, MAX(round) AS last_round
+                FROM submissions
+                WHERE address != 'unsigned'
+                GROUP BY address
+                HAVING bool_or(success) = false
+                ORDER BY submissions DESC, address",
+                &[],
+                limit,
+                offset,
+            )
+            .await?;
+
+        let mut miners = Vec::with_capacity(rows.len());
+        for row in rows {
+            let who = {
+                let val: String = row
+                    .try_get(0)
+                    .map_err(|_| Error::RowNotFound("address", 0))?;
+                Address::from_str(&val).map_err(|e| Error::Parse(e.to_string()))?
+            };
+            let submissions: i64 = row
+                .try_get(1)
+                .map_err(|_| Error::RowNotFound("submissions", 1))?;
+            let last_round = row
+                .try_get(2)
+                .map_err(|_| Error::RowNotFound("last_round", 2))?;
+
+            miners.push(NeverSuccessfulMiner {
+                who,
+                submissions: submissions as u64,
+                last_round,
+            });
+        }
+
+        Ok(Page {
+            total,
+            items: miners,
+        })
+    }
+
+    /// Get the submission counts and won elections of the signed miners, only those of `address`
+    /// and of the submissions in the time range if given.
+    ///
+    /// The miners with the most successful submissions come first.
+    pub async fn get_miner_stats(
+        &self,
+        address: Option<&Address>,
+        time_range: &TimeRange,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<MinerStats>, Error> {
+        let address = address.map(ToString::to_string);
+        let mut filter = Filter::default();
+        filter.conditions.push("address != 'unsigned'".to_string());
+        if let Some(address) = &address {
+            filter.and("address = $", address);
+        }
+        filter.time_range(time_range);
+
+        // Only the elections of the rounds the miner submitted in within the filter count as won.
+        let (total, rows) = self
+            .query_page(
+                &format!(
+                    "WITH s AS (SELECT address, round, success FROM {})
+                    SELECT
+                        s.address,
+                        COUNT(*) AS submissions,
+                        COUNT(*) FILTER (WHERE s.success) AS successful,
+                        (
+                            SELECT COUNT(*) FROM elections e
+                            WHERE e.result = 'signed'
+                                AND e.address @> jsonb_build_array(s.address)
+                                AND e.round IN (SELECT round FROM s AS r WHERE r.address = s.address)
+                        ) AS won,
+                        MAX(s.round)::BIGINT AS latest_round,
+                        {} AS latest_round_result,
+                        {} AS prev_round_result
+                    FROM s
+                    GROUP BY s.address
+                    ORDER BY successful DESC, submissions DESC, s.address",
+                    filter.from("submissions"),
+                    round_result("s.address", "MAX(s.round)::BIGINT"),
+                    round_result("s.address", "MAX(s.round)::BIGINT - 1"),
+                ),
+                &filter.params,
+                limit,
+                offset,
+            )
+            .await?;
+
+        let mut miners = Vec::with_capacity(rows.len());
+        for row in rows {
+            let who = {
+                let val: String = row
+                    .try_get(0)
+                    .map_err(|_| Error::RowNotFound("address", 0))?;
+                Address::from_str(&val).map_err(|e| Error::Parse(e.to_string()))?
+            };
+            let submissions: i64 = row
+                .try_get(1)
+                .map_err(|_| Error::RowNotFound("submissions", 1))?;
+            let successful: i64 = row
+                .try_get(2)
+                .map_err(|_| Error::RowNotFound("successful", 2))?;
+            let won: i64 = row.try_get(3).map_err(|_| Error::RowNotFound("won", 3))?;
+            let latest_round: i64 = row
+                .try_get(4)
+                .map_err(|_| Error::RowNotFound("latest_round", 4))?;
+            let latest_round_result = row
+                .try_get(5)
+                .map_err(|_| Error::RowNotFound("latest_round_result", 5))?;
+            let prev_round_result = row
+                .try_get(6)
+                .map_err(|_| Error::RowNotFound("prev_round_result", 6))?;
+
+            miners.push(MinerStats {
+                who,
+                submissions: submissions as u64,
+                successful: successful as u64,
+                failed: (submissions - successful) as u64,
+                won: won as u64,
+                latest_round: latest_round as u32,
+                latest_round_result,
+                prev_round_result,
+            });
+        }
+
+        Ok(Page {
+            total,
+            items: miners,
+        })
+    }
+
+    pub async fn get_miner_distribution(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Page<MinerDistribution>, Error> {
+        let (total, rows) = self
+            .query_page(
+                "WITH counts AS (
+                    SELECT COUNT(*) AS n FROM submissions WHERE address != 'unsigned' GROUP BY address
+                )
+                SELECT
+                    CASE
+                        WHEN n = 1 THEN '1'
+                        WHEN n <= 5 THEN '2-5'
+                        WHEN n <= 20 THEN '6-20'
+                        WHEN n <= 100 THEN '21-100'
+                        ELSE '101+'
+                    END AS bucket,
+                    COUNT(*) AS miners
+                FROM counts
+                GROUP BY bucket
+                ORDER BY MIN(n)",
+                &[],
+                limit,
+                offset,
+            )
+            .await?;
+
+        let mut distribution = Vec::with_capacity(rows.len());
+        for row in rows {
+            let submissions = row
+                .try_get(0)
+                .map_err(|_| Error::RowNotFound("bucket", 0))?;
+            let miners: i64 = row
+                .try_get(1)
+                .map_err(|_| Error::RowNotFound("miners", 1))?;
+            distribution.push(MinerDistribution {
+                submissions,
+                miners: miners as u64,
+            });
+        }
+
+        Ok(Page {
+            total,
+            items: distribution,
+        })
+    }
+
+    /// Get the win rate of `address` over the most recent `window` rounds it submitted in.
+    pub async fn get_win_rate(
+        &self,
+        address: &Address,
+        window: NonZeroU32,
+    ) -> Result<WinRate, Error> {
+        let address = address.to_string();
+        let limit = window.get() as i64;
+        let row = self.client().await?
+            .query_one(
+                "WITH rounds AS (
+                    SELECT DISTINCT round FROM submissions WHERE address = $1 ORDER BY round DESC LIMIT $2
+                )
+                SELECT COUNT(*) AS submitted, COUNT(e.id) AS won
+                FROM rounds r
+                LEFT JOIN elections e
+                    ON e.round = r.round AND e.result = 'signed' AND e.address @> jsonb_build_array($1::text)",
+                &[&address, &limit],
+            )
+            .await?;
+
+        let submitted: i64 = row
+            .try_get(0)
+            .map_err(|_| Error::RowNotFound("submitted", 0))?;
+        let won: i64 = row.try_get(1).map_err(|_| Error::RowNotFound("won", 1))?;
+        let win_rate = if submitted == 0 {
+            0.0
+        } else {
+            won as f64 / submitted as f64
+        };
+
+        Ok(WinRate {
+            window: window.get(),
+            submitted: submitted as u64,
+            won: won as u64,
+            win_rate,
+        })
+    }
+
+    /// Get the signed submissions that landed in the final `blocks` blocks before
+    /// the signed phase closed.
+    pub async fn get_snipes(&self, blocks: NonZeroU32) -> Result<Snipes, Error> {
+        let rows = self.client().await?
+            .query(
+                "SELECT s.address, s.round, s.block, t.block::BIGINT - s.block::BIGINT AS blocks_before_close
+                FROM submissions s
+                JOIN phase_transitions t ON t.round = s.round AND t.from_phase = 'signed'
+                WHERE s.address != 'unsigned'
+                    AND s.block <= t.block
+                    AND t.block::BIGINT - s.block::BIGINT <= $1
+                ORDER BY s.round, s.block",
+                &[&(blocks.get() as i64)],
+            )
+            .await?;
+
+        let mut snipes = Snipes::default();
+
+        for row in rows {
+            let who = {
+                let val: String = row
+                    .try_get(0)
+                    .map_err(|_| Error::RowNotFound("address", 0))?;
+                Address::from_str(&val).map_err(Error::Parse)?
+            };
+            let round: u32 = row.try_get(1).map_err(|_| Error::RowNotFound("round", 1))?;
+            let block = row.try_get(2).map_err(|_| Error::RowNotFound("block", 2))?;
+            let blocks_before_close: i64 = row
+                .try_get(3)
+                .map_err(|_| Error::RowNotFound("blocks_before_close", 3))?;
+
+            match snipes.per_round.last_mut() {
+                Some(r) if r.round == round => r.count += 1,
+                _ => snipes.per_round.push(RoundSnipes { round, count: 1 }),
+            }
+
+            snipes.submissions.push(Snipe {
+                who,
+                round,
+                block,
+                blocks_before_close: blocks_before_close as u32,
+            });
+        }
+
+        Ok(snipes)
+    }
+
+    /// Get the number of blocks spent in each phase of `round` from the phase transitions,
+    /// where the last phase ends at `end_block`.
+    pub async fn get_phase_durations(
+        &self,
+        round: u32,
+        end_block: u32,
+    ) -> Result<HashMap<String, u32>, Error> {
+        let rows = self
+            .client()
+            .await?
+            .query(
+                "SELECT block, to_phase FROM phase_transitions WHERE round = $1 ORDER BY block",
+                &[&round],
+            )
+            .await?;
+
+        let mut transitions = Vec::with_capacity(rows.len());
+        for row in rows {
+            let block: u32 = row.try_get(0).map_err(|_| Error::RowNotFound("block", 0))?;
+            let phase: String = row
+                .try_get(1)
+                .map_err(|_| Error::RowNotFound("to_phase", 1))?;
+            transitions.push((block, phase));
+        }
+
+        let mut durations = HashMap::new();
+        for (i, (start, phase)) in transitions.iter().enumerate() {
+            let end = transitions.get(i + 1).map_or(end_block, |(b, _)| *b);
+            *durations.entry(phase.clone()).or_default() += end.saturating_sub(*start);
+        }
+
+        Ok(durations)
+    }
+
+    /// Get the most recent round in the database, if any.
+    pub async fn get_latest_round(&self) -> Result<Option<u32>, Error> {
+        let row = self.client().await?
+            .query_one(
+                "SELECT GREATEST((SELECT MAX(round) FROM submissions), (SELECT MAX(round) FROM elections))",
+                &[],
+            )
+            .await?;
+        row.try_get(0).map_err(|_| Error::RowNotFound("round", 0))
+    }
+
+    /// Get the validators elected in `round`, `None` if the election or its winners are unknown.
+    pub async fn get_election_winners(&self, round: u32) -> Result<Option<Vec<Address>>, Error> {
+        let Some(row) = self
+            .client()
+            .await?
+            .query_opt(
+                "SELECT winners FROM elections WHERE round = $1 ORDER BY id DESC LIMIT 1",
+                &[&round],
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        addresses_from_json(
+            row.try_get(0)
+                .map_err(|_| Error::RowNotFound("winners", 0))?,
+        )
+    }
+
+    /// Store the state of the active election round of the monitor, replacing the previous state.
+    pub async fn save_round_state(&self, state: &ElectionRound) -> Result<(), Error> {
+        if self.read_only {
+            return Ok(());
+        }
+
+        let state = serde_json::to_value(state).map_err(|e| Error::Parse(e.to_string()))?;
+        self.write(
+            "INSERT INTO monitor_state (id, state, updated_at) VALUES (1, $1, now())
+            ON CONFLICT (id) DO UPDATE SET state = EXCLUDED.state, updated_at = EXCLUDED.updated_at",
+            &[&state],
+        )
+        .await
+    }
+
+    /// Get the state of the election round the monitor stored last, if any.
+    pub async fn get_round_state(&self) -> Result<Option<ElectionRound>, Error> {
+        let Some(row) = self
+            .client()
+            .await?
+            .query_opt("SELECT state FROM monitor_state WHERE id = 1", &[])
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let state: serde_json::Value =
+            row.try_get(0).map_err(|_| Error::RowNotFound("state", 0))?;
+        serde_json::from_value(state)
+            .map(Some)
+            .map_err(|e| Error::Parse(e.to_string()))
+    }
+
+    /// Delete all rows older than `round` from all tables.
+    ///
+    /// Returns the number of deleted rows per table.
+    pub async fn prune_before_round(&self, round: u32) -> Result<Vec<(&'static str, u64)>, Error> {
+        if self.dry_run(
+            "all tables",
+            &format_args!("delete rows before round {round}"),
+        ) {
+            return Ok(Vec::new());
+        }
+
+        let mut deleted = Vec::with_capacity(TABLES.len());
+
+        for table in TABLES {
+            let n = self
+                .client()
+                .await?
+                .execute(&format!("DELETE FROM {table} WHERE round < $1"), &[&round])
+                .await?;
+            deleted.push((*table, n));
+        }
+
+        Ok(deleted)
+    }
+
+    /// Delete the rows read from the block at `block` by [`crate::helpers::read_block`],
+    /// once it was replaced by another block in a reorg.
+    ///
+    /// Returns the number of deleted rows. Elections and config changes are kept.
+    pub async fn delete_block(&self, block: u32) -> Result<u64, Error> {
+        if self.dry_run("all tables", &format_args!("delete rows of block {block}")) {
+            return Ok(0);
+        }
+
+        let mut deleted = 0;
+        for table in BLOCK_TABLES {
+            deleted += self
+                .client()
+                .await?
+                .execute(&format!("DELETE FROM {table} WHERE block = $1"), &[&block])
+                .await?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Count the rows of a table, either exactly or by the planner's estimate
+    /// which is much cheaper for large tables.
+    pub async fn count_rows(&self, table: &str, estimate: bool) -> Result<u64, Error> {
+        if estimate {
+            let row = self
+                .client()
+                .await?
+                .query_one(
+                    "SELECT reltuples::BIGINT FROM pg_class WHERE relname = $1",
+                    &[&table],
+                )
+                .await?;
+            let n: i64 = row
+                .try_get(0)
+                .map_err(|_| Error::RowNotFound("reltuples", 0))?;
+            // The estimate is -1 if the table hasn't been analyzed yet.
+            Ok(n.max(0) as u64)
+        } else {
+            self.collect_count(&format!("SELECT COUNT(*) FROM {table}"))
+                .await
+        }
+    }
+
+    async fn collect_count(&self, statement: &str) -> Result<u64, Error> {
+        self.collect_count_with(statement, &[]).await
+    }
+
+    async fn collect_count_with(
+        &self,
+        statement: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, Error> {
+        let row = self.client().await?.query_one(statement, params).await?;
+        Ok(row.get::<_, i64>(0) as u64)
+    }
+
+    /// Count the submissions, only those of `address`, in the time range and of the spec version if given.
+    pub async fn count_submissions(
+        &self,
+        address: Option<&Address>,
+        time_range: &TimeRange,
+        spec_version: Option<u32>,
+    ) -> Result<u64, Error> {
+        let address = address.map(ToString::to_string);
+        let mut filter = Filter::default();
+
+        if let Some(address) = &address {
+            filter.and("address = $", address);
+        }
+        filter.time_range(time_range);
+        filter.spec_version(&spec_version);
+
+        self.collect_count_with(
+            &format!("SELECT COUNT(*) FROM {}", filter.from("submissions")),
+            &filter.params,
+        )
+        .await
+    }
+
+    /// Count the elections, only those in the time range and of the spec version if given.
+    pub async fn count_elections(
+        &self,
+        time_range: &TimeRange,
+        spec_version: Option<u32>,
+    ) -> Result<u64, Error> {
+        let mut filter = Filter::default();
+        filter.time_range(time_range);
+        filter.spec_version(&spec_version);
+
+        self.collect_count_with(
+            &format!("SELECT COUNT(*) FROM {}", filter.from("elections")),
+            &filter.params,
+        )
+        .await
+    }
+
+    /// Count the slashes, only those of `address`, of at least `min_amount` and in the time range if given.
+    pub async fn count_slashed(
+        &self,
+        address: Option<&Address>,
+        min_amount: Option<u128>,
+        time_range: &TimeRange,
+    ) -> Result<u64, Error> {
+        let address = address.map(ToString::to_string);
+        let min_amount = min_amount.map(|a| a.to_string());
+        let mut filter = Filter::default();
+
+        if let Some(address) = &address {
+            filter.and("address = $", address);
+        }
+        if let Some(min_amount) = &min_amount {
+            filter.and("amount_planck::NUMERIC >= $::TEXT::NUMERIC", min_amount);
+        }
+        filter.time_range(time_range);
+
+        self.collect_count_with(
+            &format!("SELECT COUNT(*) FROM {}", filter.from("slashed")),
+            &filter.params,
+        )
+        .await
+    }
+}
+
+/// The queries of the API and the writes of the monitor, implemented by [`Database`]
+/// and in memory for the tests.
+///
+/// The methods return boxed futures so that the routes can share any store as `Arc<dyn Store>`.
+pub trait Store: Send + Sync {
+    fn ping(&self) -> BoxFuture<'_, Result<(), Error>>;
+
+    fn subscribe(&self) -> broadcast::Receiver<Event>;
+
+    fn insert_submission(&self, submission: Submission) -> BoxFuture<'_, Result<(), Error>>;
+
+    fn insert_election(&self, election: Election) -> BoxFuture<'_, Result<(), Error>>;
+
+    fn insert_slashed(&self, slashed: Slashed) -> BoxFuture<'_, Result<(), Error>>;
+
+    fn insert_ejection(&self, ejection: Ejection) -> BoxFuture<'_, Result<(), Error>>;
+
+    fn insert_phase_transition(
+        &self,
+        transition: PhaseTransition,
+    ) -> BoxFuture<'_, Result<(), Error>>;
+
+    fn insert_config_change(&self, change: ConfigChange) -> BoxFuture<'_, Result<(), Error>>;
+
+    fn delete_block(&self, block: u32) -> BoxFuture<'_, Result<u64, Error>>;
+
+    fn get_submissions_by_addresses<'a>(
+        &'a self,
+        addresses: &'a [Address],
+    ) -> BoxFuture<'a, Result<Vec<Submission>, Error>>;
+
+    fn get_submission_by_extrinsic_hash(
+        &self,
+        hash: Hash,
+    ) -> BoxFuture<'_, Result<Option<Submission>, Error>>;
+
+    fn get_submissions_paginated<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<Submission>, Error>>;
+
+    fn get_submissions_by_min_stake(
+        &self,
+        threshold: u128,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Submission>, Error>>;
+
+    fn get_success_submissions_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Submission>, Error>>;
+
+    fn get_failed_submissions_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Submission>, Error>>;
+
+    fn get_elections_paginated<'a>(
+        &'a self,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        result: Option<ElectionResultKind>,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<Election>, Error>>;
+
+    fn get_unsigned_despite_signed_elections_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Election>, Error>>;
+
+    fn get_slashed_paginated<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        min_amount: Option<u128>,
+        time_range: &'a TimeRange,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<Slashed>, Error>>;
+
+    fn get_ejections_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Ejection>, Error>>;
+
+    fn get_config_changes_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<ConfigChange>, Error>>;
+
+    fn get_submissions_after<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        after: i32,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Vec<(i32, Submission)>, Error>>;
+
+    fn get_elections_after<'a>(
+        &'a self,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        after: i32,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Vec<(i32, Election)>, Error>>;
+
+    fn get_most_recent_submissions(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> BoxFuture<'_, Result<Vec<Submission>, Error>>;
+
+    fn get_most_recent_elections(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> BoxFuture<'_, Result<Vec<Election>, Error>>;
+
+    fn get_most_recent_slashed(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> BoxFuture<'_, Result<Vec<Slashed>, Error>>;
+
+    fn get_elections_with_submissions(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<ElectionWithSubmissions>, Error>>;
+
+    fn get_round_activity(&self, round: u32)
+        -> BoxFuture<'_, Result<Option<RoundActivity>, Error>>;
+
+    fn get_stats(&self) -> BoxFuture<'_, Result<Stats, Error>>;
+
+    fn get_never_successful_miners(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<NeverSuccessfulMiner>, Error>>;
+
+    fn get_miner_stats<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<MinerStats>, Error>>;
+
+    fn get_miner_distribution(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<MinerDistribution>, Error>>;
+
+    fn get_win_rate<'a>(
+        &'a self,
+        address: &'a Address,
+        window: NonZeroU32,
+    ) -> BoxFuture<'a, Result<WinRate, Error>>;
+
+    fn get_snipes(&self, blocks: NonZeroU32) -> BoxFuture<'_, Result<Snipes, Error>>;
+
+    fn get_phase_durations(
+        &self,
+        round: u32,
+        end_block: u32,
+    ) -> BoxFuture<'_, Result<HashMap<String, u32>, Error>>;
+
+    fn get_election_winners(
+        &self,
+        round: u32,
+    ) -> BoxFuture<'_, Result<Option<Vec<Address>>, Error>>;
+
+    fn count_submissions<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+    ) -> BoxFuture<'a, Result<u64, Error>>;
+
+    fn count_elections<'a>(
+        &'a self,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+    ) -> BoxFuture<'a, Result<u64, Error>>;
+
+    fn count_slashed<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        min_amount: Option<u128>,
+        time_range: &'a TimeRange,
+    ) -> BoxFuture<'a, Result<u64, Error>>;
+}
+
+/// A store shared by the routes.
+pub type SharedStore = Arc<dyn Store>;
+
+impl Store for Database {
+    fn ping(&self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(Database::ping(self))
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Event> {
+        Database::subscribe(self)
+    }
+
+    fn insert_submission(&self, submission: Submission) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(Database::insert_submission(self, submission))
+    }
+
+    fn insert_election(&self, election: Election) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(Database::insert_election(self, election))
+    }
+
+    fn insert_slashed(&self, slashed: Slashed) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(Database::insert_slashed(self, slashed))
+    }
+
+    fn insert_ejection(&self, ejection: Ejection) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(Database::insert_ejection(self, ejection))
+    }
+
+    fn insert_phase_transition(
+        &self,
+        transition: PhaseTransition,
+    ) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(Database::insert_phase_transition(self, transition))
+    }
+
+    fn insert_config_change(&self, change: ConfigChange) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(Database::insert_config_change(self, change))
+    }
+
+    fn delete_block(&self, block: u32) -> BoxFuture<'_, Result<u64, Error>> {
+        Box::pin(Database::delete_block(self, block))
+    }
+
+    fn get_submissions_by_addresses<'a>(
+        &'a self,
+        addresses: &'a [Address],
+    ) -> BoxFuture<'a, Result<Vec<Submission>, Error>> {
+        Box::pin(Database::get_submissions_by_addresses(self, addresses))
+    }
+
+    fn get_submission_by_extrinsic_hash(
+        &self,
+        hash: Hash,
+    ) -> BoxFuture<'_, Result<Option<Submission>, Error>> {
+        Box::pin(Database::get_submission_by_extrinsic_hash(self, hash))
+    }
+
+    fn get_submissions_paginated<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<Submission>, Error>> {
+        Box::pin(Database::get_submissions_paginated(
+            self,
+            address,
+            time_range,
+            spec_version,
+            limit,
+            offset,
+        ))
+    }
+
+    fn get_submissions_by_min_stake(
+        &self,
+        threshold: u128,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Submission>, Error>> {
+        Box::pin(Database::get_submissions_by_min_stake(
+            self, threshold, limit, offset,
+        ))
+    }
+
+    fn get_success_submissions_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Submission>, Error>> {
+        Box::pin(Database::get_success_submissions_paginated(
+            self, limit, offset,
+        ))
+    }
+
+    fn get_failed_submissions_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Submission>, Error>> {
+        Box::pin(Database::get_failed_submissions_paginated(
+            self, limit, offset,
+        ))
+    }
+
+    fn get_elections_paginated<'a>(
+        &'a self,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        result: Option<ElectionResultKind>,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<Election>, Error>> {
+        Box::pin(Database::get_elections_paginated(
+            self,
+            time_range,
+            spec_version,
+            result,
+            limit,
+            offset,
+        ))
+    }
+
+    fn get_unsigned_despite_signed_elections_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Election>, Error>> {
+        Box::pin(Database::get_unsigned_despite_signed_elections_paginated(
+            self, limit, offset,
+        ))
+    }
+
+    fn get_slashed_paginated<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        min_amount: Option<u128>,
+        time_range: &'a TimeRange,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<Slashed>, Error>> {
+        Box::pin(Database::get_slashed_paginated(
+            self, address, min_amount, time_range, limit, offset,
+        ))
+    }
+
+    fn get_ejections_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Ejection>, Error>> {
+        Box::pin(Database::get_ejections_paginated(self, limit, offset))
+    }
+
+    fn get_config_changes_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<ConfigChange>, Error>> {
+        Box::pin(Database::get_config_changes_paginated(self, limit, offset))
+    }
+
+    fn get_submissions_after<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        after: i32,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Vec<(i32, Submission)>, Error>> {
+        Box::pin(Database::get_submissions_after(
+            self,
+            address,
+            time_range,
+            spec_version,
+            after,
+            limit,
+            offset,
+        ))
+    }
+
+    fn get_elections_after<'a>(
+        &'a self,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        after: i32,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Vec<(i32, Election)>, Error>> {
+        Box::pin(Database::get_elections_after(
+            self,
+            time_range,
+            spec_version,
+            after,
+            limit,
+            offset,
+        ))
+    }
+
+    fn get_most_recent_submissions(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> BoxFuture<'_, Result<Vec<Submission>, Error>> {
+        Box::pin(Database::get_most_recent_submissions(self, n, sort))
+    }
+
+    fn get_most_recent_elections(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> BoxFuture<'_, Result<Vec<Election>, Error>> {
+        Box::pin(Database::get_most_recent_elections(self, n, sort))
+    }
+
+    fn get_most_recent_slashed(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> BoxFuture<'_, Result<Vec<Slashed>, Error>> {
+        Box::pin(Database::get_most_recent_slashed(self, n, sort))
+    }
+
+    fn get_elections_with_submissions(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<ElectionWithSubmissions>, Error>> {
+        Box::pin(Database::get_elections_with_submissions(
+            self, limit, offset,
+        ))
+    }
+
+    fn get_round_activity(
+        &self,
+        round: u32,
+    ) -> BoxFuture<'_, Result<Option<RoundActivity>, Error>> {
+        Box::pin(Database::get_round_activity(self, round))
+    }
+
+    fn get_stats(&self) -> BoxFuture<'_, Result<Stats, Error>> {
+        Box::pin(Database::get_stats(self))
     }
 
-    pub async fn get_most_recent_elections(&self, n: NonZeroUsize) -> Result<Vec<Election>, Error> {
-        collect_db_rows(
-            self.0
-                .query(
-                    &format!("SELECT * FROM elections ORDER BY round DESC LIMIT {n}"),
-                    &[],
-                )
-                .await?,
-        )
+    fn get_never_successful_miners(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<NeverSuccessfulMiner>, Error>> {
+        Box::pin(Database::get_never_successful_miners(self, limit, offset))
     }
 
-    pub async fn get_most_recent_slashed(&self, n: NonZeroUsize) -> Result<Vec<Slashed>, Error> {
-        collect_db_rows(
-            self.0
-                .query(
-                    &format!("SELECT * FROM slashed ORDER BY round DESC LIMIT {n}"),
-                    &[],
-                )
-                .await?,
-        )
+    fn get_miner_stats<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<MinerStats>, Error>> {
+        Box::pin(Database::get_miner_stats(
+            self, address, time_range, limit, offset,
+        ))
     }
 
-    pub async fn get_stats(&self) -> Result<Stats, Error> {
-        let submissions = self
-            .collect_count("SELECT COUNT(*) FROM submissions")
-            .await?;
+    fn get_miner_distribution(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<MinerDistribution>, Error>> {
+        Box::pin(Database::get_miner_distribution(self, limit, offset))
+    }
 
-        let submissions_failed = self
-            .collect_count("SELECT COUNT(*) FROM submissions WHERE success = false")
-            .await?;
+    fn get_win_rate<'a>(
+        &'a self,
+        address: &'a Address,
+        window: NonZeroU32,
+    ) -> BoxFuture<'a, Result<WinRate, Error>> {
+        Box::pin(Database::get_win_rate(self, address, window))
+    }
 
-        let submissions_success = self
-            .collect_count("SELECT COUNT(*) FROM submissions WHERE success = true")
-            .await?;
+    fn get_snipes(&self, blocks: NonZeroU32) -> BoxFuture<'_, Result<Snipes, Error>> {
+        Box::pin(Database::get_snipes(self, blocks))
+    }
 
-        let elections = self.collect_count("SELECT COUNT(*) FROM elections").await?;
+    fn get_phase_durations(
+        &self,
+        round: u32,
+        end_block: u32,
+    ) -> BoxFuture<'_, Result<HashMap<String, u32>, Error>> {
+        Box::pin(Database::get_phase_durations(self, round, end_block))
+    }
 
-        let elections_failed = self
-            .collect_count("SELECT COUNT(*) FROM elections WHERE result = 'election failed'")
-            .await?;
+    fn get_election_winners(
+        &self,
+        round: u32,
+    ) -> BoxFuture<'_, Result<Option<Vec<Address>>, Error>> {
+        Box::pin(Database::get_election_winners(self, round))
+    }
 
-        let elections_signed = self
-            .collect_count("SELECT COUNT(*) FROM elections WHERE result = 'signed'")
-            .await?;
+    fn count_submissions<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+    ) -> BoxFuture<'a, Result<u64, Error>> {
+        Box::pin(Database::count_submissions(
+            self,
+            address,
+            time_range,
+            spec_version,
+        ))
+    }
 
-        let elections_unsigned = self
-            .collect_count("SELECT COUNT(*) FROM elections WHERE result = 'unsigned'")
-            .await?;
+    fn count_elections<'a>(
+        &'a self,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+    ) -> BoxFuture<'a, Result<u64, Error>> {
+        Box::pin(Database::count_elections(self, time_range, spec_version))
+    }
 
-        let slashed = self.collect_count("SELECT COUNT(*) FROM slashed").await?;
+    fn count_slashed<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        min_amount: Option<u128>,
+        time_range: &'a TimeRange,
+    ) -> BoxFuture<'a, Result<u64, Error>> {
+        Box::pin(Database::count_slashed(
+            self, address, min_amount, time_range,
+        ))
+    }
+}
 
-        Ok(Stats {
-            submissions: Submissions {
-                total: submissions,
-                failed: submissions_failed,
-                success: submissions_success,
-            },
-            elections: Elections {
-                total: elections,
-                failed: elections_failed,
-                signed: elections_signed,
-                unsigned: elections_unsigned,
-            },
-            slashed,
+/// Addresses are stored as a JSON array of hex encoded public keys.
+fn addresses_to_json(addresses: Option<&[Address]>) -> Option<Json<Vec<String>>> {
+    addresses.map(|a| Json(a.iter().map(ToString::to_string).collect()))
+}
+
+/// Parse a JSON array of addresses, `null` is the same as no array.
+fn addresses_from_json(
+    val: Option<Json<Option<Vec<String>>>>,
+) -> Result<Option<Vec<Address>>, Error> {
+    val.and_then(|v| v.0)
+        .map(|addresses| {
+            addresses
+                .iter()
+                .map(|a| Address::from_str(a))
+                .collect::<Result<Vec<_>, _>>()
         })
-    }
+        .transpose()
+        .map_err(Error::Parse)
+}
 
-    async fn collect_count(&self, statement: &str) -> Result<u64, Error> {
-        let row = self.0.query_one(statement, &[]).await?;
-        Ok(row.get::<_, i64>(0) as u64)
-    }
+/// The SQL expression whether `address` `won`, `lost` or was `absent` in `round`,
+/// i.e. didn't submit in it.
+fn round_result(address: &str, round: &str) -> String {
+    format!(
+        "CASE
+            WHEN NOT EXISTS (
+                SELECT 1 FROM submissions p WHERE p.address = {address} AND p.round::BIGINT = {round}
+            ) THEN 'absent'
+            WHEN EXISTS (
+                SELECT 1 FROM elections e
+                WHERE e.round::BIGINT = {round} AND e.result = 'signed' AND e.address @> jsonb_build_array({address})
+            ) THEN 'won'
+            ELSE 'lost'
+        END"
+    )
 }
 
 fn collect_db_rows<T>(rows: Vec<tokio_postgres::Row>) -> Result<Vec<T>, Error>
@@ -265,25 +2425,134 @@ where
     Ok(items)
 }
 
+/// The score of a solution, the stakes are in planck.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Score {
+    /// The smallest backing stake of the elected validators, higher is better.
+    pub minimal_stake: u128,
+    /// The sum of the backing stakes of the elected validators, higher is better.
+    pub sum_stake: u128,
+    /// The sum of the squared backing stakes of the elected validators, lower is better.
+    pub sum_stake_squared: u128,
+}
+
+impl From<ElectionScore> for Score {
+    fn from(score: ElectionScore) -> Self {
+        Self {
+            minimal_stake: score.minimal_stake,
+            sum_stake: score.sum_stake,
+            sum_stake_squared: score.sum_stake_squared,
+        }
+    }
+}
+
+// There is no schema for `u128`, the stakes are serialized as JSON integers which may not fit
+// into 64 bits, so they are documented as integers without the `int64` format.
+impl OaSchema for Score {
+    fn schema() -> oasgen::Schema {
+        let mut schema = oasgen::Schema::new_object();
+        for field in ["minimal_stake", "sum_stake", "sum_stake_squared"] {
+            let mut stake = oasgen::Schema::new_integer();
+            stake.data.description = Some("A u128 in planck, may exceed 64 bits.".to_string());
+            schema.properties_mut().insert(field, stake);
+            schema.required_mut().push(field.to_string());
+        }
+        schema
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, OaSchema)]
 pub struct Submission {
     who: Address,
     round: u32,
     block: u32,
-    score: serde_json::Value,
+    score: Score,
     success: bool,
+    /// The hash of the extrinsic that submitted the solution.
+    extrinsic_hash: Option<String>,
+    /// Whether the solution was computed against the snapshot of a previous round.
+    stale_snapshot: Option<bool>,
+    /// The index of the extrinsic in the block, i.e. the order the submissions of a block were processed in.
+    extrinsic_index: Option<u32>,
+    /// Why the submission failed, e.g. `stale_snapshot` or `queue_full`.
+    failure_reason: Option<String>,
+    /// The on-chain timestamp of the block.
+    #[serde(with = "time::serde::rfc3339::option")]
+    created_at: Option<OffsetDateTime>,
+    /// The number of blocks between the submission and the finalization of the election,
+    /// `None` until the election is finalized and if it failed.
+    blocks_to_finalize: Option<u32>,
+    /// The runtime spec version at the block, `None` if unknown.
+    spec_version: Option<u32>,
+    /// The number of voters in the solution, `None` if unknown.
+    solution_voters: Option<u32>,
+    /// The number of distinct winners in the solution, `None` if unknown.
+    solution_winners: Option<u32>,
+    /// Whether the same address `won`, `lost` or was `absent` in the previous round.
+    ///
+    /// Only returned by `POST /submissions/by-addresses`, the miner endpoints return it for
+    /// the miner's most recent round.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prev_round_result: Option<String>,
 }
 
 impl Submission {
+    pub fn who(&self) -> &Address {
+        &self.who
+    }
+
     pub fn new(who: Address, round: u32, block: u32, score: ElectionScore, success: bool) -> Self {
         Self {
             who,
             round,
             block,
-            score: serde_json::to_value(score).expect("ElectionScore serialize infallible; qed"),
+            score: score.into(),
             success,
+            extrinsic_hash: None,
+            stale_snapshot: None,
+            extrinsic_index: None,
+            failure_reason: None,
+            created_at: None,
+            blocks_to_finalize: None,
+            spec_version: None,
+            solution_voters: None,
+            solution_winners: None,
+            prev_round_result: None,
         }
     }
+
+    pub fn with_extrinsic(mut self, index: u32, hash: Hash) -> Self {
+        self.extrinsic_index = Some(index);
+        self.extrinsic_hash = Some(format!("{hash:?}"));
+        self
+    }
+
+    pub fn with_stale_snapshot(mut self, stale_snapshot: bool) -> Self {
+        self.stale_snapshot = Some(stale_snapshot);
+        self
+    }
+
+    pub fn with_failure_reason(mut self, reason: FailureReason) -> Self {
+        self.failure_reason = Some(reason.to_string());
+        self
+    }
+
+    pub fn with_created_at(mut self, created_at: OffsetDateTime) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    pub fn with_spec_version(mut self, spec_version: Option<u32>) -> Self {
+        self.spec_version = spec_version;
+        self
+    }
+
+    /// Set the number of voters and winners of the solution.
+    pub fn with_solution_size(mut self, size: Option<(u32, u32)>) -> Self {
+        self.solution_voters = size.map(|(voters, _)| voters);
+        self.solution_winners = size.map(|(_, winners)| winners);
+        self
+    }
 }
 
 impl TryFrom<Row> for Submission {
@@ -298,10 +2567,47 @@ impl TryFrom<Row> for Submission {
         };
         let round = row.try_get(2).map_err(|_| Error::RowNotFound("round", 2))?;
         let block = row.try_get(3).map_err(|_| Error::RowNotFound("block", 3))?;
-        let score = row.try_get(4).map_err(|_| Error::RowNotFound("score", 4))?;
+        let score = {
+            let val: Json<Score> = row.try_get(4).map_err(|_| Error::RowNotFound("score", 4))?;
+            val.0
+        };
         let success = row
             .try_get(5)
             .map_err(|_| Error::RowNotFound("success", 5))?;
+        let extrinsic_hash = row
+            .try_get(6)
+            .map_err(|_| Error::RowNotFound("extrinsic_hash", 6))?;
+        let stale_snapshot = row
+            .try_get(7)
+            .map_err(|_| Error::RowNotFound("stale_snapshot", 7))?;
+        let extrinsic_index = row
+            .try_get(8)
+            .map_err(|_| Error::RowNotFound("extrinsic_index", 8))?;
+        let failure_reason = row
+            .try_get(9)
+            .map_err(|_| Error::RowNotFound("failure_reason", 9))?;
+        let created_at = row
+            .try_get(10)
+            .map_err(|_| Error::RowNotFound("created_at", 10))?;
+        let blocks_to_finalize = row
+            .try_get(11)
+            .map_err(|_| Error::RowNotFound("blocks_to_finalize", 11))?;
+        let spec_version = row
+            .try_get(12)
+            .map_err(|_| Error::RowNotFound("spec_version", 12))?;
+        let solution_voters = row
+            .try_get(13)
+            .map_err(|_| Error::RowNotFound("solution_voters", 13))?;
+        let solution_winners = row
+            .try_get(14)
+            .map_err(|_| Error::RowNotFound("solution_winners", 14))?;
+        // Only selected by `get_submissions_by_addresses`.
+        let prev_round_result = if row.len() > 15 {
+            row.try_get(15)
+                .map_err(|_| Error::RowNotFound("prev_round_result", 15))?
+        } else {
+            None
+        };
 
         Ok(Self {
             who,
@@ -309,6 +2615,16 @@ impl TryFrom<Row> for Submission {
             block,
             score,
             success,
+            extrinsic_hash,
+            stale_snapshot,
+            extrinsic_index,
+            failure_reason,
+            created_at,
+            blocks_to_finalize,
+            spec_version,
+            solution_voters,
+            solution_winners,
+            prev_round_result,
         })
     }
 }
@@ -316,10 +2632,29 @@ impl TryFrom<Row> for Submission {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, OaSchema)]
 pub struct Election {
     result: String,
-    winner: serde_json::Value,
+    /// The rewarded signed submitters, `None` if the election wasn't based on a signed solution.
+    winner: Option<Vec<Address>>,
     round: u32,
     block: u32,
-    score: serde_json::Value,
+    score: Score,
+    /// The author of the block in which the election was finalized.
+    block_author: Option<Address>,
+    /// Whether the number of elected winners matched the desired number of targets,
+    /// `None` if unknown.
+    met_target: Option<bool>,
+    /// The number of blocks spent in each phase of the round, e.g. `signed` and `unsigned`.
+    phase_durations: Option<HashMap<String, u32>>,
+    /// The number of voters in the election snapshot.
+    snapshot_voters: Option<u32>,
+    /// The number of targets in the election snapshot.
+    snapshot_targets: Option<u32>,
+    /// The on-chain timestamp of the block.
+    #[serde(with = "time::serde::rfc3339::option")]
+    created_at: Option<OffsetDateTime>,
+    /// The validators elected by the solution, `None` if unknown.
+    winners: Option<Vec<Address>>,
+    /// The runtime spec version at the block, `None` if unknown.
+    spec_version: Option<u32>,
 }
 
 impl Election {
@@ -330,12 +2665,10 @@ impl Election {
         score: ElectionScore,
     ) -> Self {
         let (result, winner) = match election {
-            InnerElectionResult::Signed(addr) => (
-                "signed".to_string(),
-                serde_json::to_value(&addr).expect("AccountId serialize infallible; qed"),
-            ),
-            InnerElectionResult::Unsigned => ("unsigned".to_string(), json!(null)),
-            InnerElectionResult::Failed => ("election failed".to_string(), json!(null)),
+            InnerElectionResult::Signed(winners) => ("signed".to_string(), Some(winners)),
+            InnerElectionResult::Unsigned => ("unsigned".to_string(), None),
+            InnerElectionResult::Failed => ("election failed".to_string(), None),
+            InnerElectionResult::GovernanceFallback => ("governance fallback".to_string(), None),
         };
 
         Self {
@@ -343,9 +2676,57 @@ impl Election {
             winner,
             round,
             block,
-            score: serde_json::to_value(score).expect("ElectionScore serialize infallible; qed"),
+            score: score.into(),
+            block_author: None,
+            met_target: None,
+            phase_durations: None,
+            snapshot_voters: None,
+            snapshot_targets: None,
+            created_at: None,
+            winners: None,
+            spec_version: None,
         }
     }
+
+    pub fn is_failed(&self) -> bool {
+        self.result == "election failed"
+    }
+
+    pub fn with_block_author(mut self, block_author: Option<Address>) -> Self {
+        self.block_author = block_author;
+        self
+    }
+
+    pub fn with_met_target(mut self, met_target: Option<bool>) -> Self {
+        self.met_target = met_target;
+        self
+    }
+
+    pub fn with_phase_durations(mut self, phase_durations: Option<HashMap<String, u32>>) -> Self {
+        self.phase_durations = phase_durations;
+        self
+    }
+
+    pub fn with_snapshot_size(mut self, size: Option<(u32, u32)>) -> Self {
+        self.snapshot_voters = size.map(|(voters, _)| voters);
+        self.snapshot_targets = size.map(|(_, targets)| targets);
+        self
+    }
+
+    pub fn with_created_at(mut self, created_at: OffsetDateTime) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    pub fn with_winners(mut self, winners: Option<Vec<Address>>) -> Self {
+        self.winners = winners;
+        self
+    }
+
+    pub fn with_spec_version(mut self, spec_version: Option<u32>) -> Self {
+        self.spec_version = spec_version;
+        self
+    }
 }
 
 impl TryFrom<Row> for Election {
@@ -355,12 +2736,49 @@ impl TryFrom<Row> for Election {
         let result = row
             .try_get(1)
             .map_err(|_| Error::RowNotFound("result", 1))?;
-        let winner: serde_json::Value = row
-            .try_get(2)
-            .map_err(|_| Error::RowNotFound("address", 2))?;
+        let winner = addresses_from_json(
+            row.try_get(2)
+                .map_err(|_| Error::RowNotFound("address", 2))?,
+        )?;
         let round = row.try_get(3).map_err(|_| Error::RowNotFound("round", 3))?;
         let block = row.try_get(4).map_err(|_| Error::RowNotFound("block", 4))?;
-        let score = row.try_get(5).map_err(|_| Error::RowNotFound("score", 5))?;
+        let score = {
+            let val: Json<Score> = row.try_get(5).map_err(|_| Error::RowNotFound("score", 5))?;
+            val.0
+        };
+        let block_author = {
+            let val: Option<String> = row
+                .try_get(6)
+                .map_err(|_| Error::RowNotFound("block_author", 6))?;
+            val.map(|v| Address::from_str(&v))
+                .transpose()
+                .map_err(Error::Parse)?
+        };
+        let met_target = row
+            .try_get(7)
+            .map_err(|_| Error::RowNotFound("met_target", 7))?;
+        let phase_durations = {
+            let val: Option<Json<HashMap<String, u32>>> = row
+                .try_get(8)
+                .map_err(|_| Error::RowNotFound("phase_durations", 8))?;
+            val.map(|v| v.0)
+        };
+        let snapshot_voters = row
+            .try_get(9)
+            .map_err(|_| Error::RowNotFound("snapshot_voters", 9))?;
+        let snapshot_targets = row
+            .try_get(10)
+            .map_err(|_| Error::RowNotFound("snapshot_targets", 10))?;
+        let created_at = row
+            .try_get(11)
+            .map_err(|_| Error::RowNotFound("created_at", 11))?;
+        let winners = addresses_from_json(
+            row.try_get(12)
+                .map_err(|_| Error::RowNotFound("winners", 12))?,
+        )?;
+        let spec_version = row
+            .try_get(13)
+            .map_err(|_| Error::RowNotFound("spec_version", 13))?;
 
         Ok(Self {
             result,
@@ -368,16 +2786,46 @@ impl TryFrom<Row> for Election {
             round,
             block,
             score,
+            block_author,
+            met_target,
+            phase_durations,
+            snapshot_voters,
+            snapshot_targets,
+            created_at,
+            winners,
+            spec_version,
         })
     }
 }
 
+/// Everything recorded in a round.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, OaSchema)]
+pub struct RoundActivity {
+    /// The election of the round, `None` if it wasn't finalized yet.
+    election: Option<Election>,
+    submissions: Vec<Submission>,
+    slashed: Vec<Slashed>,
+}
+
+/// An election together with the submissions of its round.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, OaSchema)]
+pub struct ElectionWithSubmissions {
+    election: Election,
+    submissions: Vec<Submission>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, OaSchema)]
 pub struct Slashed {
     pub who: Address,
     pub round: u32,
     pub block: u32,
-    pub amount: String,
+    /// The slashed amount in planck.
+    pub amount_planck: String,
+    /// The slashed amount in the chain's native token, `None` if its decimals aren't known.
+    pub amount_tokens: Option<String>,
+    /// The on-chain timestamp of the block.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
 }
 
 impl TryFrom<Row> for Slashed {
@@ -390,17 +2838,27 @@ impl TryFrom<Row> for Slashed {
                 .map_err(|_| Error::RowNotFound("address", 1))?;
             Address::from_str(&val).map_err(|e| Error::Parse(e.to_string()))?
         };
-        let amount = row
+        let amount_planck: String = row
             .try_get(2)
-            .map_err(|_| Error::RowNotFound("amount", 2))?;
+            .map_err(|_| Error::RowNotFound("amount_planck", 2))?;
         let round = row.try_get(3).map_err(|_| Error::RowNotFound("round", 3))?;
         let block = row.try_get(4).map_err(|_| Error::RowNotFound("block", 4))?;
+        let created_at = row
+            .try_get(5)
+            .map_err(|_| Error::RowNotFound("created_at", 5))?;
+        // Slashes stored before the token amount was, or while the decimals weren't known.
+        let amount_tokens = row
+            .try_get::<_, Option<String>>(6)
+            .map_err(|_| Error::RowNotFound("amount_tokens", 6))?
+            .or_else(|| amount_planck.parse().ok().and_then(format_tokens));
 
         Ok(Self {
             who,
-            amount,
+            amount_planck,
+            amount_tokens,
             round,
             block,
+            created_at,
         })
     }
 }
@@ -416,11 +2874,73 @@ impl Slashed {
             who: Address::from_bytes(who.0.as_ref()),
             round,
             block,
-            amount: amount.to_string(),
+            amount_planck: amount.to_string(),
+            amount_tokens: format_tokens(amount),
+            created_at: None,
+        }
+    }
+
+    pub fn with_created_at(mut self, created_at: OffsetDateTime) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+}
+
+/// The submissions grouped by the addresses as they were sent and the addresses that couldn't be parsed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, OaSchema)]
+pub struct SubmissionsByAddress {
+    pub submissions: HashMap<String, Vec<Submission>>,
+    pub invalid: Vec<String>,
+}
+
+/// A signed solution that was ejected from the queue by a better solution.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, OaSchema)]
+pub struct Ejection {
+    pub who: Address,
+    pub ejected_by: Address,
+    pub round: u32,
+    pub block: u32,
+}
+
+impl Ejection {
+    pub fn new(who: Address, ejected_by: Address, round: u32, block: u32) -> Self {
+        Self {
+            who,
+            ejected_by,
+            round,
+            block,
         }
     }
 }
 
+impl TryFrom<Row> for Ejection {
+    type Error = Error;
+
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        let who = {
+            let val: String = row
+                .try_get(1)
+                .map_err(|_| Error::RowNotFound("address", 1))?;
+            Address::from_str(&val).map_err(|e| Error::Parse(e.to_string()))?
+        };
+        let ejected_by = {
+            let val: String = row
+                .try_get(2)
+                .map_err(|_| Error::RowNotFound("ejected_by", 2))?;
+            Address::from_str(&val).map_err(|e| Error::Parse(e.to_string()))?
+        };
+        let round = row.try_get(3).map_err(|_| Error::RowNotFound("round", 3))?;
+        let block = row.try_get(4).map_err(|_| Error::RowNotFound("block", 4))?;
+
+        Ok(Self {
+            who,
+            ejected_by,
+            round,
+            block,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, OaSchema)]
 pub struct Stats {
     submissions: Submissions,
@@ -433,6 +2953,8 @@ pub struct Submissions {
     total: u64,
     failed: u64,
     success: u64,
+    /// The number of failed submissions per failure reason.
+    failure_reasons: HashMap<String, u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, OaSchema)]
@@ -441,4 +2963,238 @@ pub struct Elections {
     failed: u64,
     signed: u64,
     unsigned: u64,
+    governance_fallback: u64,
+}
+
+/// The number of miners whose submission count falls within a bucket.
+#[derive(Debug, Clone, Serialize, Deserialize, OaSchema)]
+pub struct MinerDistribution {
+    /// The range of submissions, e.g. `2-5`.
+    submissions: String,
+    /// The number of miners that submitted within that range.
+    miners: u64,
+}
+
+/// A signed miner without any successful submission.
+#[derive(Debug, Clone, Serialize, Deserialize, OaSchema)]
+pub struct NeverSuccessfulMiner {
+    who: Address,
+    /// The number of failed submissions.
+    submissions: u64,
+    /// The most recent round the miner submitted in.
+    last_round: u32,
+}
+
+/// The submissions and won elections of a signed miner.
+#[derive(Debug, Clone, Serialize, Deserialize, OaSchema)]
+pub struct MinerStats {
+    who: Address,
+    /// The number of submissions.
+    submissions: u64,
+    /// The number of successful submissions.
+    successful: u64,
+    /// The number of failed submissions.
+    failed: u64,
+    /// The number of elections won with a signed solution of the miner.
+    won: u64,
+    /// The most recent round the miner submitted in.
+    latest_round: u32,
+    /// Whether the miner `won` or `lost` its most recent round.
+    latest_round_result: String,
+    /// Whether the miner `won`, `lost` or was `absent` in the round before its most recent one.
+    prev_round_result: String,
+}
+
+/// The win rate of a miner over its most recent rounds.
+#[derive(Debug, Clone, Serialize, Deserialize, OaSchema)]
+pub struct WinRate {
+    /// The maximum number of rounds looked at.
+    window: u32,
+    /// The number of rounds the miner submitted in, at most `window`.
+    submitted: u64,
+    /// The number of those rounds the miner won.
+    won: u64,
+    /// `won / submitted`, 0 if the miner didn't submit at all.
+    win_rate: f64,
+}
+
+/// A transition of the election phase, e.g. from `signed` to `unsigned`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, OaSchema)]
+pub struct PhaseTransition {
+    pub round: u32,
+    pub block: u32,
+    pub from: String,
+    pub to: String,
+}
+
+impl PhaseTransition {
+    pub fn new(round: u32, block: u32, from: &str, to: &str) -> Self {
+        Self {
+            round,
+            block,
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+}
+
+/// A call that affects the elections, e.g. a change of the validator count.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, OaSchema)]
+pub struct ConfigChange {
+    /// The call as `Pallet::call`.
+    pub call: String,
+    /// The signer of the extrinsic, `None` if it was unsigned.
+    pub signer: Option<Address>,
+    pub round: u32,
+    pub block: u32,
+    /// The decoded arguments of the call.
+    pub args: String,
+}
+
+impl ConfigChange {
+    pub fn new(
+        call: String,
+        signer: Option<Address>,
+        round: u32,
+        block: u32,
+        args: String,
+    ) -> Self {
+        Self {
+            call,
+            signer,
+            round,
+            block,
+            args,
+        }
+    }
+}
+
+impl TryFrom<Row> for ConfigChange {
+    type Error = Error;
+
+    fn try_from(row: Row) -> Result<Self, Self::Error> {
+        let call = row.try_get(1).map_err(|_| Error::RowNotFound("call", 1))?;
+        let signer = {
+            let val: Option<String> = row
+                .try_get(2)
+                .map_err(|_| Error::RowNotFound("signer", 2))?;
+            val.map(|v| Address::from_str(&v))
+                .transpose()
+                .map_err(Error::Parse)?
+        };
+        let round = row.try_get(3).map_err(|_| Error::RowNotFound("round", 3))?;
+        let block = row.try_get(4).map_err(|_| Error::RowNotFound("block", 4))?;
+        let args = row.try_get(5).map_err(|_| Error::RowNotFound("args", 5))?;
+
+        Ok(Self {
+            call,
+            signer,
+            round,
+            block,
+            args,
+        })
+    }
+}
+
+/// A signed submission that landed shortly before the signed phase closed.
+#[derive(Debug, Clone, Serialize, Deserialize, OaSchema)]
+pub struct Snipe {
+    who: Address,
+    round: u32,
+    block: u32,
+    /// The number of blocks between the submission and the end of the signed phase.
+    blocks_before_close: u32,
+}
+
+/// The number of late submissions in a round.
+#[derive(Debug, Clone, Serialize, Deserialize, OaSchema)]
+pub struct RoundSnipes {
+    round: u32,
+    count: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, OaSchema)]
+pub struct Snipes {
+    submissions: Vec<Snipe>,
+    per_round: Vec<RoundSnipes>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deadpool_postgres::TimeoutType;
+    use std::cell::Cell;
+
+    fn timeout() -> Error {
+        Error::Pool(PoolError::Timeout(TimeoutType::Wait))
+    }
+
+    #[test]
+    fn transient_errors() {
+        assert!(timeout().is_transient());
+        assert!(!Error::Pool(PoolError::Closed).is_transient());
+        assert!(!Error::Parse("invalid".to_string()).is_transient());
+        assert!(!Error::RowNotFound("round", 0).is_transient());
+
+        for code in [
+            "08006", "40001", "40P01", "53300", "57P01", "57P02", "57P03",
+        ] {
+            assert!(is_transient_code(code), "{code}");
+        }
+        // A unique violation, an undefined table, a cancelled query and a rolled back constraint violation.
+        for code in ["23505", "42P01", "57014", "40002"] {
+            assert!(!is_transient_code(code), "{code}");
+        }
+    }
+
+    #[test]
+    fn score_schema_has_no_int64_format() {
+        let schema = serde_json::to_value(Score::schema()).unwrap();
+        let stake = &schema["properties"]["sum_stake_squared"];
+
+        assert_eq!(stake["type"], "integer");
+        assert!(stake.get("format").is_none(), "{stake}");
+    }
+
+    #[tokio::test]
+    async fn retry_stops_after_the_attempts() {
+        let attempts = Cell::new(0);
+        let res: Result<(), _> = retry(NonZeroU32::new(3).unwrap(), Duration::ZERO, || async {
+            attempts.set(attempts.get() + 1);
+            Err(timeout())
+        })
+        .await;
+
+        assert!(matches!(res, Err(Error::Pool(PoolError::Timeout(_)))));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_only_transient_errors() {
+        let attempts = Cell::new(0);
+        let res: Result<(), _> = retry(NonZeroU32::new(3).unwrap(), Duration::ZERO, || async {
+            attempts.set(attempts.get() + 1);
+            Err(Error::Parse("invalid".to_string()))
+        })
+        .await;
+
+        assert!(matches!(res, Err(Error::Parse(_))));
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_until_success() {
+        let attempts = Cell::new(0);
+        let res = retry(NonZeroU32::new(3).unwrap(), Duration::ZERO, || async {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 2 {
+                Err(timeout())
+            } else {
+                Ok(attempts.get())
+            }
+        })
+        .await;
+
+        assert_eq!(res.unwrap(), 2);
+    }
 }
@@ -0,0 +1,804 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! A [`Store`] that keeps the rows in memory, for the tests of the routes and the inserts.
+//!
+//! The queries follow the SQL of [`Database`], the id of a row is its position in its table.
+
+use super::*;
+use futures::future::{self, FutureExt};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Default)]
+struct Tables {
+    submissions: Vec<Submission>,
+    elections: Vec<Election>,
+    slashed: Vec<Slashed>,
+    ejections: Vec<Ejection>,
+    phase_transitions: Vec<PhaseTransition>,
+    config_changes: Vec<ConfigChange>,
+}
+
+pub struct MemoryStore {
+    tables: Mutex<Tables>,
+    events: broadcast::Sender<Event>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+        Self {
+            tables: Mutex::new(Tables::default()),
+            events,
+        }
+    }
+}
+
+impl MemoryStore {
+    /// The submissions inserted so far, in the order they were inserted in.
+    pub fn submissions(&self) -> Vec<Submission> {
+        self.tables().submissions.clone()
+    }
+
+    fn tables(&self) -> std::sync::MutexGuard<'_, Tables> {
+        self.tables
+            .lock()
+            .expect("no test panics while holding the lock; qed")
+    }
+
+    /// Run `f` on the tables, the future is ready right away.
+    fn query<'a, T: Send + 'a>(
+        &self,
+        f: impl FnOnce(&mut Tables) -> T,
+    ) -> BoxFuture<'a, Result<T, Error>> {
+        let res = f(&mut self.tables());
+        future::ready(Ok(res)).boxed()
+    }
+
+    fn insert(
+        &self,
+        event: Event,
+        f: impl FnOnce(&mut Tables),
+    ) -> BoxFuture<'_, Result<(), Error>> {
+        f(&mut self.tables());
+        _ = self.events.send(event);
+        future::ready(Ok(())).boxed()
+    }
+}
+
+/// Whether `created_at` is in the range, a row without a timestamp only matches an unbounded range.
+fn in_range(created_at: Option<OffsetDateTime>, range: &TimeRange) -> bool {
+    let after_from = match (range.from, created_at) {
+        (Some(from), Some(t)) => t >= from,
+        (Some(_), None) => false,
+        (None, _) => true,
+    };
+    let before_to = match (range.to, created_at) {
+        (Some(to), Some(t)) => t <= to,
+        (Some(_), None) => false,
+        (None, _) => true,
+    };
+    after_from && before_to
+}
+
+fn page<T>(items: Vec<T>, limit: u32, offset: u32) -> Page<T> {
+    Page {
+        total: items.len() as u64,
+        items: items
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect(),
+    }
+}
+
+/// Up to `limit` rows with an id above `after` after skipping `offset` of them, with their ids.
+fn chunk<T: Clone>(
+    rows: &[T],
+    filter: impl Fn(&T) -> bool,
+    after: i32,
+    limit: u32,
+    offset: u32,
+) -> Vec<(i32, T)> {
+    rows.iter()
+        .enumerate()
+        .map(|(i, row)| (i as i32 + 1, row))
+        .filter(|(id, row)| *id > after && filter(row))
+        .skip(offset as usize)
+        .take(limit as usize)
+        .map(|(id, row)| (id, row.clone()))
+        .collect()
+}
+
+/// The `n` most recent rows by the round or block of `sort`, in its order.
+fn most_recent<T: Clone>(
+    rows: &[T],
+    n: NonZeroUsize,
+    sort: Sort,
+    key: impl Fn(&T) -> (u32, u32),
+) -> Vec<T> {
+    let column = |row: &T| match sort.column {
+        SortColumn::Round => key(row).0,
+        SortColumn::Block => key(row).1,
+    };
+    let mut recent = rows.to_vec();
+    recent.sort_by_key(|row| std::cmp::Reverse(column(row)));
+    recent.truncate(n.get());
+    if sort.order == SortOrder::Asc {
+        recent.reverse();
+    }
+    recent
+}
+
+fn won(election: &Election, address: &Address) -> bool {
+    election.result == "signed"
+        && election
+            .winner
+            .as_ref()
+            .is_some_and(|winners| winners.contains(address))
+}
+
+impl Tables {
+    /// The last election of `round`.
+    fn election(&self, round: u32) -> Option<&Election> {
+        self.elections.iter().rev().find(|e| e.round == round)
+    }
+
+    /// The submissions of `round` in the order they were processed in.
+    fn round_submissions(&self, round: u32) -> Vec<Submission> {
+        let mut submissions: Vec<_> = self
+            .submissions
+            .iter()
+            .filter(|s| s.round == round)
+            .cloned()
+            .collect();
+        submissions.sort_by_key(|s| (s.block, s.extrinsic_index));
+        submissions
+    }
+
+    /// See [`round_result`].
+    fn round_result(&self, address: &Address, round: i64) -> String {
+        let result = if !self
+            .submissions
+            .iter()
+            .any(|s| &s.who == address && s.round as i64 == round)
+        {
+            "absent"
+        } else if self
+            .elections
+            .iter()
+            .any(|e| e.round as i64 == round && won(e, address))
+        {
+            "won"
+        } else {
+            "lost"
+        };
+        result.to_string()
+    }
+
+    /// The signed submissions grouped by their address, ordered by the address.
+    fn by_miner(&self, filter: impl Fn(&Submission) -> bool) -> BTreeMap<String, Vec<&Submission>> {
+        let mut miners: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for s in self.submissions.iter() {
+            if s.who != Address::unsigned() && filter(s) {
+                miners.entry(s.who.to_string()).or_default().push(s);
+            }
+        }
+        miners
+    }
+
+    fn submissions_filter<'a>(
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+    ) -> impl Fn(&Submission) -> bool + 'a {
+        move |s| {
+            address.is_none_or(|a| &s.who == a)
+                && in_range(s.created_at, time_range)
+                && spec_version.is_none_or(|v| s.spec_version == Some(v))
+        }
+    }
+
+    fn elections_filter(
+        time_range: &TimeRange,
+        spec_version: Option<u32>,
+    ) -> impl Fn(&Election) -> bool + '_ {
+        move |e| {
+            in_range(e.created_at, time_range)
+                && spec_version.is_none_or(|v| e.spec_version == Some(v))
+        }
+    }
+
+    fn slashed_filter<'a>(
+        address: Option<&'a Address>,
+        min_amount: Option<u128>,
+        time_range: &'a TimeRange,
+    ) -> impl Fn(&Slashed) -> bool + 'a {
+        move |s| {
+            address.is_none_or(|a| &s.who == a)
+                && min_amount
+                    .is_none_or(|min| s.amount_planck.parse::<u128>().is_ok_and(|a| a >= min))
+                && in_range(s.created_at, time_range)
+        }
+    }
+}
+
+fn filtered<T: Clone>(rows: &[T], filter: impl Fn(&T) -> bool) -> Vec<T> {
+    rows.iter().filter(|row| filter(row)).cloned().collect()
+}
+
+impl Store for MemoryStore {
+    fn ping(&self) -> BoxFuture<'_, Result<(), Error>> {
+        future::ready(Ok(())).boxed()
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    fn insert_submission(&self, submission: Submission) -> BoxFuture<'_, Result<(), Error>> {
+        self.insert(Event::Submission(submission.clone()), |t| {
+            t.submissions.push(Submission {
+                blocks_to_finalize: None,
+                prev_round_result: None,
+                ..submission
+            })
+        })
+    }
+
+    fn insert_election(&self, election: Election) -> BoxFuture<'_, Result<(), Error>> {
+        self.insert(Event::Election(election.clone()), |t| {
+            if !election.is_failed() {
+                for s in t.submissions.iter_mut() {
+                    if s.round == election.round && s.block <= election.block {
+                        s.blocks_to_finalize = Some(election.block - s.block);
+                    }
+                }
+            }
+            t.elections.push(election);
+        })
+    }
+
+    fn insert_slashed(&self, slashed: Slashed) -> BoxFuture<'_, Result<(), Error>> {
+        self.insert(Event::Slashed(slashed.clone()), |t| t.slashed.push(slashed))
+    }
+
+    fn insert_ejection(&self, ejection: Ejection) -> BoxFuture<'_, Result<(), Error>> {
+        self.insert(Event::Ejection(ejection.clone()), |t| {
+            t.ejections.push(ejection)
+        })
+    }
+
+    fn insert_phase_transition(
+        &self,
+        transition: PhaseTransition,
+    ) -> BoxFuture<'_, Result<(), Error>> {
+        self.query(|t| t.phase_transitions.push(transition))
+    }
+
+    fn insert_config_change(&self, change: ConfigChange) -> BoxFuture<'_, Result<(), Error>> {
+        self.query(|t| t.config_changes.push(change))
+    }
+
+    fn delete_block(&self, block: u32) -> BoxFuture<'_, Result<u64, Error>> {
+        self.query(|t| {
+            let before = t.submissions.len()
+                + t.slashed.len()
+                + t.ejections.len()
+                + t.phase_transitions.len();
+            t.submissions.retain(|s| s.block != block);
+            t.slashed.retain(|s| s.block != block);
+            t.ejections.retain(|e| e.block != block);
+            t.phase_transitions.retain(|p| p.block != block);
+            let after = t.submissions.len()
+                + t.slashed.len()
+                + t.ejections.len()
+                + t.phase_transitions.len();
+            (before - after) as u64
+        })
+    }
+
+    fn get_submissions_by_addresses<'a>(
+        &'a self,
+        addresses: &'a [Address],
+    ) -> BoxFuture<'a, Result<Vec<Submission>, Error>> {
+        self.query(|t| {
+            t.submissions
+                .iter()
+                .filter(|s| addresses.contains(&s.who))
+                .map(|s| Submission {
+                    prev_round_result: Some(t.round_result(&s.who, s.round as i64 - 1)),
+                    ..s.clone()
+                })
+                .collect()
+        })
+    }
+
+    fn get_submission_by_extrinsic_hash(
+        &self,
+        hash: Hash,
+    ) -> BoxFuture<'_, Result<Option<Submission>, Error>> {
+        let hash = format!("{hash:?}");
+        self.query(move |t| {
+            t.submissions
+                .iter()
+                .find(|s| s.extrinsic_hash.as_ref() == Some(&hash))
+                .cloned()
+        })
+    }
+
+    fn get_submissions_paginated<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<Submission>, Error>> {
+        self.query(|t| {
+            let filter = Tables::submissions_filter(address, time_range, spec_version);
+            page(filtered(&t.submissions, filter), limit, offset)
+        })
+    }
+
+    fn get_submissions_by_min_stake(
+        &self,
+        threshold: u128,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Submission>, Error>> {
+        self.query(|t| {
+            let mut weak = filtered(&t.submissions, |s| s.score.minimal_stake < threshold);
+            weak.sort_by_key(|s| s.score.minimal_stake);
+            page(weak, limit, offset)
+        })
+    }
+
+    fn get_success_submissions_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Submission>, Error>> {
+        self.query(|t| page(filtered(&t.submissions, |s| s.success), limit, offset))
+    }
+
+    fn get_failed_submissions_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Submission>, Error>> {
+        self.query(|t| page(filtered(&t.submissions, |s| !s.success), limit, offset))
+    }
+
+    fn get_elections_paginated<'a>(
+        &'a self,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        result: Option<ElectionResultKind>,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<Election>, Error>> {
+        self.query(|t| {
+            let filter = Tables::elections_filter(time_range, spec_version);
+            let elections = filtered(&t.elections, |e| {
+                filter(e) && result.is_none_or(|r| e.result == r.as_sql())
+            });
+            page(elections, limit, offset)
+        })
+    }
+
+    fn get_unsigned_despite_signed_elections_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Election>, Error>> {
+        self.query(|t| {
+            let elections = filtered(&t.elections, |e| {
+                e.result == "unsigned"
+                    && t.submissions
+                        .iter()
+                        .any(|s| s.round == e.round && s.who != Address::unsigned())
+            });
+            page(elections, limit, offset)
+        })
+    }
+
+    fn get_slashed_paginated<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        min_amount: Option<u128>,
+        time_range: &'a TimeRange,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<Slashed>, Error>> {
+        self.query(|t| {
+            let filter = Tables::slashed_filter(address, min_amount, time_range);
+            let mut slashed = filtered(&t.slashed, filter);
+            if min_amount.is_some() {
+                slashed.sort_by_key(|s| std::cmp::Reverse(s.amount_planck.parse::<u128>().ok()));
+            }
+            page(slashed, limit, offset)
+        })
+    }
+
+    fn get_ejections_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<Ejection>, Error>> {
+        self.query(|t| page(t.ejections.clone(), limit, offset))
+    }
+
+    fn get_config_changes_paginated(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<ConfigChange>, Error>> {
+        self.query(|t| page(t.config_changes.clone(), limit, offset))
+    }
+
+    fn get_submissions_after<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        after: i32,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Vec<(i32, Submission)>, Error>> {
+        self.query(|t| {
+            let filter = Tables::submissions_filter(address, time_range, spec_version);
+            chunk(&t.submissions, filter, after, limit, offset)
+        })
+    }
+
+    fn get_elections_after<'a>(
+        &'a self,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+        after: i32,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Vec<(i32, Election)>, Error>> {
+        self.query(|t| {
+            let filter = Tables::elections_filter(time_range, spec_version);
+            chunk(&t.elections, filter, after, limit, offset)
+        })
+    }
+
+    fn get_most_recent_submissions(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> BoxFuture<'_, Result<Vec<Submission>, Error>> {
+        self.query(|t| most_recent(&t.submissions, n, sort, |s| (s.round, s.block)))
+    }
+
+    fn get_most_recent_elections(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> BoxFuture<'_, Result<Vec<Election>, Error>> {
+        self.query(|t| most_recent(&t.elections, n, sort, |e| (e.round, e.block)))
+    }
+
+    fn get_most_recent_slashed(
+        &self,
+        n: NonZeroUsize,
+        sort: Sort,
+    ) -> BoxFuture<'_, Result<Vec<Slashed>, Error>> {
+        self.query(|t| most_recent(&t.slashed, n, sort, |s| (s.round, s.block)))
+    }
+
+    fn get_elections_with_submissions(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<ElectionWithSubmissions>, Error>> {
+        self.query(|t| {
+            let mut elections = t.elections.clone();
+            elections.sort_by_key(|e| std::cmp::Reverse(e.round));
+            let Page { total, items } = page(elections, limit, offset);
+            let items = items
+                .into_iter()
+                .map(|election| ElectionWithSubmissions {
+                    submissions: t.round_submissions(election.round),
+                    election,
+                })
+                .collect();
+            Page { total, items }
+        })
+    }
+
+    fn get_round_activity(
+        &self,
+        round: u32,
+    ) -> BoxFuture<'_, Result<Option<RoundActivity>, Error>> {
+        self.query(|t| {
+            let activity = RoundActivity {
+                election: t.election(round).cloned(),
+                submissions: t.round_submissions(round),
+                slashed: filtered(&t.slashed, |s| s.round == round),
+            };
+            let empty = activity.election.is_none()
+                && activity.submissions.is_empty()
+                && activity.slashed.is_empty();
+            (!empty).then_some(activity)
+        })
+    }
+
+    fn get_stats(&self) -> BoxFuture<'_, Result<Stats, Error>> {
+        self.query(|t| {
+            let count_submissions = |f: &dyn Fn(&Submission) -> bool| {
+                t.submissions.iter().filter(|s| f(s)).count() as u64
+            };
+            let count_elections =
+                |result: &str| t.elections.iter().filter(|e| e.result == result).count() as u64;
+
+            let mut failure_reasons = HashMap::new();
+            for reason in t
+                .submissions
+                .iter()
+                .filter_map(|s| s.failure_reason.clone())
+            {
+                *failure_reasons.entry(reason).or_default() += 1;
+            }
+
+            Stats {
+                submissions: Submissions {
+                    total: t.submissions.len() as u64,
+                    failed: count_submissions(&|s| !s.success),
+                    success: count_submissions(&|s| s.success),
+                    failure_reasons,
+                },
+                elections: Elections {
+                    total: t.elections.len() as u64,
+                    failed: count_elections("election failed"),
+                    signed: count_elections("signed"),
+                    unsigned: count_elections("unsigned"),
+                    governance_fallback: count_elections("governance fallback"),
+                },
+                slashed: t.slashed.len() as u64,
+            }
+        })
+    }
+
+    fn get_never_successful_miners(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<NeverSuccessfulMiner>, Error>> {
+        self.query(|t| {
+            let mut miners: Vec<_> = t
+                .by_miner(|_| true)
+                .into_values()
+                .filter(|submissions| !submissions.iter().any(|s| s.success))
+                .map(|submissions| NeverSuccessfulMiner {
+                    who: submissions[0].who.clone(),
+                    submissions: submissions.len() as u64,
+                    last_round: submissions
+                        .iter()
+                        .map(|s| s.round)
+                        .max()
+                        .unwrap_or_default(),
+                })
+                .collect();
+            // Stable, so the miners with as many submissions stay ordered by address.
+            miners.sort_by_key(|m| std::cmp::Reverse(m.submissions));
+            page(miners, limit, offset)
+        })
+    }
+
+    fn get_miner_stats<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'a, Result<Page<MinerStats>, Error>> {
+        self.query(|t| {
+            let filter = Tables::submissions_filter(address, time_range, None);
+            let mut miners: Vec<_> = t
+                .by_miner(filter)
+                .into_values()
+                .map(|submissions| {
+                    let who = submissions[0].who.clone();
+                    let successful = submissions.iter().filter(|s| s.success).count() as u64;
+                    let won = t
+                        .elections
+                        .iter()
+                        .filter(|e| won(e, &who) && submissions.iter().any(|s| s.round == e.round))
+                        .count() as u64;
+                    let latest_round = submissions
+                        .iter()
+                        .map(|s| s.round)
+                        .max()
+                        .unwrap_or_default();
+
+                    MinerStats {
+                        submissions: submissions.len() as u64,
+                        successful,
+                        failed: submissions.len() as u64 - successful,
+                        won,
+                        latest_round,
+                        latest_round_result: t.round_result(&who, latest_round as i64),
+                        prev_round_result: t.round_result(&who, latest_round as i64 - 1),
+                        who,
+                    }
+                })
+                .collect();
+            miners.sort_by_key(|m| std::cmp::Reverse((m.successful, m.submissions)));
+            page(miners, limit, offset)
+        })
+    }
+
+    fn get_miner_distribution(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> BoxFuture<'_, Result<Page<MinerDistribution>, Error>> {
+        self.query(|t| {
+            let mut buckets: BTreeMap<usize, (&str, u64)> = BTreeMap::new();
+            for submissions in t.by_miner(|_| true).into_values() {
+                let (min, bucket) = match submissions.len() {
+                    1 => (1, "1"),
+                    2..=5 => (2, "2-5"),
+                    6..=20 => (6, "6-20"),
+                    21..=100 => (21, "21-100"),
+                    _ => (101, "101+"),
+                };
+                buckets.entry(min).or_insert((bucket, 0)).1 += 1;
+            }
+            let distribution = buckets
+                .into_values()
+                .map(|(bucket, miners)| MinerDistribution {
+                    submissions: bucket.to_string(),
+                    miners,
+                })
+                .collect();
+            page(distribution, limit, offset)
+        })
+    }
+
+    fn get_win_rate<'a>(
+        &'a self,
+        address: &'a Address,
+        window: NonZeroU32,
+    ) -> BoxFuture<'a, Result<WinRate, Error>> {
+        self.query(|t| {
+            let mut rounds: Vec<u32> = t
+                .submissions
+                .iter()
+                .filter(|s| &s.who == address)
+                .map(|s| s.round)
+                .collect();
+            rounds.sort_unstable_by(|a, b| b.cmp(a));
+            rounds.dedup();
+            rounds.truncate(window.get() as usize);
+
+            let submitted = rounds.len() as u64;
+            let won = rounds
+                .iter()
+                .filter(|r| {
+                    t.elections
+                        .iter()
+                        .any(|e| e.round == **r && won(e, address))
+                })
+                .count() as u64;
+
+            WinRate {
+                window: window.get(),
+                submitted,
+                won,
+                win_rate: if submitted == 0 {
+                    0.0
+                } else {
+                    won as f64 / submitted as f64
+                },
+            }
+        })
+    }
+
+    fn get_snipes(&self, blocks: NonZeroU32) -> BoxFuture<'_, Result<Snipes, Error>> {
+        self.query(|t| {
+            let mut late = Vec::new();
+            for s in t
+                .submissions
+                .iter()
+                .filter(|s| s.who != Address::unsigned())
+            {
+                for close in t
+                    .phase_transitions
+                    .iter()
+                    .filter(|p| p.round == s.round && p.from == "signed" && s.block <= p.block)
+                {
+                    if close.block - s.block <= blocks.get() {
+                        late.push(Snipe {
+                            who: s.who.clone(),
+                            round: s.round,
+                            block: s.block,
+                            blocks_before_close: close.block - s.block,
+                        });
+                    }
+                }
+            }
+            late.sort_by_key(|s| (s.round, s.block));
+
+            let mut snipes = Snipes::default();
+            for snipe in late {
+                match snipes.per_round.last_mut() {
+                    Some(r) if r.round == snipe.round => r.count += 1,
+                    _ => snipes.per_round.push(RoundSnipes {
+                        round: snipe.round,
+                        count: 1,
+                    }),
+                }
+                snipes.submissions.push(snipe);
+            }
+            snipes
+        })
+    }
+
+    fn get_phase_durations(
+        &self,
+        round: u32,
+        end_block: u32,
+    ) -> BoxFuture<'_, Result<HashMap<String, u32>, Error>> {
+        self.query(|t| {
+            let mut transitions = filtered(&t.phase_transitions, |p| p.round == round);
+            transitions.sort_by_key(|p| p.block);
+
+            let mut durations = HashMap::new();
+            for (i, transition) in transitions.iter().enumerate() {
+                let end = transitions.get(i + 1).map_or(end_block, |p| p.block);
+                *durations.entry(transition.to.clone()).or_default() +=
+                    end.saturating_sub(transition.block);
+            }
+            durations
+        })
+    }
+
+    fn get_election_winners(
+        &self,
+        round: u32,
+    ) -> BoxFuture<'_, Result<Option<Vec<Address>>, Error>> {
+        self.query(|t| t.election(round).and_then(|e| e.winners.clone()))
+    }
+
+    fn count_submissions<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+    ) -> BoxFuture<'a, Result<u64, Error>> {
+        self.query(|t| {
+            let filter = Tables::submissions_filter(address, time_range, spec_version);
+            t.submissions.iter().filter(|s| filter(s)).count() as u64
+        })
+    }
+
+    fn count_elections<'a>(
+        &'a self,
+        time_range: &'a TimeRange,
+        spec_version: Option<u32>,
+    ) -> BoxFuture<'a, Result<u64, Error>> {
+        self.query(|t| {
+            let filter = Tables::elections_filter(time_range, spec_version);
+            t.elections.iter().filter(|e| filter(e)).count() as u64
+        })
+    }
+
+    fn count_slashed<'a>(
+        &'a self,
+        address: Option<&'a Address>,
+        min_amount: Option<u128>,
+        time_range: &'a TimeRange,
+    ) -> BoxFuture<'a, Result<u64, Error>> {
+        self.query(|t| {
+            let filter = Tables::slashed_filter(address, min_amount, time_range);
+            t.slashed.iter().filter(|s| filter(s)).count() as u64
+        })
+    }
+}
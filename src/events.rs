@@ -0,0 +1,79 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::db::{Ejection, Election, Slashed, Submission};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// The number of events buffered for slow subscribers before they start to miss events.
+pub const EVENTS_CAPACITY: usize = 1024;
+
+/// A row that was inserted into the database, broadcast to the live event stream.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Event {
+    Submission(Submission),
+    Election(Election),
+    Slashed(Slashed),
+    Ejection(Ejection),
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::Submission(_) => EventKind::Submission,
+            Self::Election(e) if e.is_failed() => EventKind::ElectionFailed,
+            Self::Election(_) => EventKind::Election,
+            Self::Slashed(_) => EventKind::Slashed,
+            Self::Ejection(_) => EventKind::Ejection,
+        }
+    }
+}
+
+/// The type of an [`Event`] which can be used to filter the live event stream.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    Submission,
+    Election,
+    ElectionFailed,
+    Slashed,
+    Ejection,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 5] = [
+        Self::Submission,
+        Self::Election,
+        Self::ElectionFailed,
+        Self::Slashed,
+        Self::Ejection,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Submission => "submission",
+            Self::Election => "election",
+            Self::ElectionFailed => "election_failed",
+            Self::Slashed => "slashed",
+            Self::Ejection => "ejection",
+        }
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s.trim())
+            .ok_or_else(|| {
+                let valid: Vec<_> = Self::ALL.iter().map(EventKind::as_str).collect();
+                format!(
+                    "Invalid event type `{s}`, expected one of: {}",
+                    valid.join(", ")
+                )
+            })
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::db::Database;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum Table {
+    Submissions,
+    Elections,
+    Slashed,
+    Ejections,
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct ExportOpts {
+    /// The table to export.
+    #[clap(long, value_enum)]
+    table: Table,
+    /// The format to export the table in.
+    #[clap(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+    /// The file to write to, defaults to stdout.
+    #[clap(long)]
+    output: Option<PathBuf>,
+}
+
+/// Write all rows of a table to a file or stdout.
+pub async fn run(db: Database, opts: ExportOpts) -> anyhow::Result<()> {
+    let ExportOpts {
+        table,
+        format,
+        output,
+    } = opts;
+
+    let rows = match table {
+        Table::Submissions => to_values(db.get_all_submissions().await?)?,
+        Table::Elections => to_values(db.get_all_elections().await?)?,
+        Table::Slashed => to_values(db.get_all_slashed().await?)?,
+        Table::Ejections => to_values(db.get_all_ejections().await?)?,
+    };
+
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    match format {
+        Format::Json => serde_json::to_writer_pretty(&mut out, &rows)?,
+        Format::Csv => write_csv(&mut out, &rows)?,
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+fn to_values<T: Serialize>(rows: Vec<T>) -> Result<Vec<Value>, serde_json::Error> {
+    rows.into_iter().map(serde_json::to_value).collect()
+}
+
+/// Write JSON objects as CSV where the columns are the fields of the first object.
+///
+/// Nested values such as the score are written as JSON.
+pub fn write_csv(w: &mut impl Write, rows: &[Value]) -> io::Result<()> {
+    let Some(first) = rows.first() else {
+        return Ok(());
+    };
+    let columns = csv_columns(first);
+
+    write_csv_header(w, &columns)?;
+    for row in rows {
+        write_csv_row(w, &columns, row)?;
+    }
+
+    Ok(())
+}
+
+/// The CSV columns of a JSON object, its fields.
+pub fn csv_columns(row: &Value) -> Vec<String> {
+    match row {
+        Value::Object(fields) => fields.keys().cloned().collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn write_csv_header(w: &mut impl Write, columns: &[String]) -> io::Result<()> {
+    let header: Vec<String> = columns.iter().map(|c| csv_field(c)).collect();
+    writeln!(w, "{}", header.join(","))
+}
+
+/// Write the fields of a JSON object in the order of `columns`, missing fields are left empty.
+pub fn write_csv_row(w: &mut impl Write, columns: &[String], row: &Value) -> io::Result<()> {
+    let fields: Vec<String> = columns
+        .iter()
+        .map(|c| match row.get(c.as_str()) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => csv_field(s),
+            Some(v) => csv_field(&v.to_string()),
+        })
+        .collect();
+    writeln!(w, "{}", fields.join(","))
+}
+
+/// Replace the object `field` of a JSON object by a field per key named `{field}_{key}`,
+/// e.g. `score` by `score_minimal_stake`, `score_sum_stake` and `score_sum_stake_squared`.
+pub fn flatten(mut row: Value, field: &str) -> Value {
+    if let Value::Object(fields) = &mut row {
+        if let Some(Value::Object(nested)) = fields.get(field).cloned() {
+            fields.remove(field);
+            for (key, value) in nested {
+                fields.insert(format!("{field}_{key}"), value);
+            }
+        }
+    }
+    row
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
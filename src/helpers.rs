@@ -2,24 +2,34 @@
 // This file is dual-licensed as Apache-2.0 or GPL-3.0.
 // see LICENSE for license details.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
-use crate::db::{self, Slashed, Submission};
+use crate::db::{self, ConfigChange, Ejection, PhaseTransition, Slashed, Store, Submission};
 use crate::types::runtime;
 use crate::types::runtime::election_provider_multi_phase::events::ElectionFinalized;
+use crate::types::runtime::runtime_types::pallet_election_provider_multi_phase::ElectionCompute;
+use crate::types::runtime::runtime_types::sp_runtime::{DispatchError, ModuleError};
 use crate::types::{
-    Address, ChainClient, Client, ElectionRound, EpmPhase, ExtrinsicDetails, Hash, Header, HeaderT,
-    EPM_PALLET_NAME,
+    Address, ChainClient, Client, ElectionRound, EpmPhase, ExtrinsicDetails, FailureReason, Hash,
+    Header, HeaderT, RpcClient, EPM_PALLET_NAME,
 };
-use crate::LOG_TARGET;
+use crate::{ingestion, prometheus, LOG_TARGET};
 
 use codec::Decode;
+use polkadot_sdk::pallet_election_provider_multi_phase::Phase;
 use polkadot_sdk::sp_npos_elections::ElectionScore;
 use scale_info::PortableRegistry;
 use scale_info::TypeInfo;
+use subxt::backend::{BlockRef, StreamOf, StreamOfResults};
+use subxt::client::UpgradeError;
+use subxt::config::substrate::DigestItem;
 use subxt::dynamic::At;
 use subxt::ext::scale_encode::EncodeAsType;
-use tokio::sync::mpsc;
+use subxt::ext::scale_value::{Value, ValueDef};
+use subxt::utils::{AccountId32, MultiAddress};
+use time::OffsetDateTime;
+use tokio::sync::{mpsc, watch};
 
 pub async fn get_phase(client: &Client, block_hash: Hash) -> anyhow::Result<EpmPhase> {
     client
@@ -45,6 +55,208 @@ pub async fn get_round(client: &Client, block_hash: Hash) -> anyhow::Result<u32>
         .map_err(Into::into)
 }
 
+/// The signed submissions queue as stored on-chain, sorted from the weakest to the best solution.
+pub struct SignedQueue(Vec<(ElectionScore, u32, Address)>);
+
+impl SignedQueue {
+    pub async fn fetch(client: &Client, block_hash: Hash) -> anyhow::Result<Self> {
+        let storage = client.chain_api().storage().at(block_hash);
+        let indices = storage
+            .fetch_or_default(
+                &runtime::storage()
+                    .election_provider_multi_phase()
+                    .signed_submission_indices(),
+            )
+            .await?;
+
+        let mut queue = Vec::with_capacity(indices.0.len());
+        for (score, block, idx) in indices.0 {
+            let submission = storage
+                .fetch(
+                    &runtime::storage()
+                        .election_provider_multi_phase()
+                        .signed_submissions_map(idx),
+                )
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Signed submission {idx} not found"))?;
+            queue.push((
+                score.0,
+                block,
+                Address::from_bytes(submission.who.0.as_slice()),
+            ));
+        }
+
+        Ok(Self(queue))
+    }
+
+    /// The strongest solution in the queue, which is the one that would be verified first.
+    pub fn best(&self) -> Option<&(ElectionScore, u32, Address)> {
+        self.0.last()
+    }
+
+    /// Insert a new solution while keeping the same ordering as the EPM pallet.
+    pub fn insert(&mut self, score: ElectionScore, block: u32, who: Address) {
+        self.0.push((score, block, who));
+        self.0.sort_by(
+            |(score1, bn1, _), (score2, bn2, _)| match score1.cmp(score2) {
+                std::cmp::Ordering::Equal => bn1.cmp(bn2).reverse(),
+                x => x,
+            },
+        );
+    }
+
+    /// Insert a new solution which ejected the weakest one and return the ejected submitter.
+    pub fn eject(&mut self, score: ElectionScore, block: u32, who: Address) -> Option<Address> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let (_, _, ejected) = self.0.remove(0);
+        self.insert(score, block, who);
+        Some(ejected)
+    }
+}
+
+const BABE_ENGINE_ID: [u8; 4] = *b"BABE";
+
+/// Get the on-chain timestamp of a block in milliseconds.
+pub async fn get_timestamp(client: &Client, block_hash: Hash) -> anyhow::Result<u64> {
+    let timestamp = client
+        .chain_api()
+        .storage()
+        .at(block_hash)
+        .fetch_or_default(&runtime::storage().timestamp().now())
+        .await?;
+
+    Ok(timestamp)
+}
+
+/// The runtime spec version at the block, `None` with a warning if it can't be read.
+pub async fn get_spec_version(client: &Client, block_hash: Hash) -> Option<u32> {
+    match client
+        .rpc()
+        .state_get_runtime_version(Some(block_hash))
+        .await
+    {
+        Ok(version) => Some(version.spec_version),
+        Err(e) => {
+            tracing::warn!(target: LOG_TARGET, block_hash = ?block_hash, "Failed to get the spec version: {e}");
+            None
+        }
+    }
+}
+
+/// Convert a `Timestamp::now` value in milliseconds into a date time.
+pub fn to_date_time(timestamp: u64) -> anyhow::Result<OffsetDateTime> {
+    Ok(OffsetDateTime::from_unix_timestamp_nanos(
+        timestamp as i128 * 1_000_000,
+    )?)
+}
+
+/// Get the validator that authored the block from the BABE pre-runtime digest.
+///
+/// Returns `None` if the block wasn't authored with BABE.
+pub async fn get_block_author(client: &Client, block: &Header) -> anyhow::Result<Option<Address>> {
+    let authority_index = block.digest.logs.iter().find_map(|log| match log {
+        // All variants of the BABE `PreDigest` start with the authority index.
+        DigestItem::PreRuntime(BABE_ENGINE_ID, data) => {
+            data.get(1..).and_then(|mut d| u32::decode(&mut d).ok())
+        }
+        _ => None,
+    });
+
+    let Some(authority_index) = authority_index else {
+        return Ok(None);
+    };
+
+    let validators = client
+        .chain_api()
+        .storage()
+        .at(block.hash())
+        .fetch_or_default(&runtime::storage().session().validators())
+        .await?;
+
+    Ok(validators
+        .get(authority_index as usize)
+        .map(|v| Address::from_bytes(v.0.as_slice())))
+}
+
+/// Get the number of winners of the queued solution and the desired number of targets.
+///
+/// This must be read before the election is finalized, i.e. at the parent of the
+/// block in which the election was finalized. Returns `None` if there is no queued solution,
+/// such as when the fallback computes the solution on-chain.
+pub async fn get_election_targets(client: &Client, at: Hash) -> anyhow::Result<Option<(u32, u32)>> {
+    let storage = client.chain_api().storage().at(at);
+
+    let Some(queued) = storage
+        .fetch(
+            &runtime::storage()
+                .election_provider_multi_phase()
+                .queued_solution(),
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let Some(desired_targets) = storage
+        .fetch(
+            &runtime::storage()
+                .election_provider_multi_phase()
+                .desired_targets(),
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some((queued.supports.0.len() as u32, desired_targets)))
+}
+
+/// Get the validators elected by the queued solution, `None` if no solution is queued.
+///
+/// Like [`get_election_targets`], this must be read at the parent of the block that finalized the election.
+pub async fn get_elected(client: &Client, at: Hash) -> anyhow::Result<Option<Vec<Address>>> {
+    let Some(queued) = client
+        .chain_api()
+        .storage()
+        .at(at)
+        .fetch(
+            &runtime::storage()
+                .election_provider_multi_phase()
+                .queued_solution(),
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(
+        queued
+            .supports
+            .0
+            .iter()
+            .map(|(who, _)| Address::from_bytes(who.0.as_ref()))
+            .collect(),
+    ))
+}
+
+/// Get the number of voters and targets of the election snapshot, `None` if there is no snapshot.
+pub async fn get_snapshot_size(client: &Client, at: Hash) -> anyhow::Result<Option<(u32, u32)>> {
+    let size = client
+        .chain_api()
+        .storage()
+        .at(at)
+        .fetch(
+            &runtime::storage()
+                .election_provider_multi_phase()
+                .snapshot_metadata(),
+        )
+        .await?;
+
+    Ok(size.map(|s| (s.voters, s.targets)))
+}
+
 /// Represent the result of reading a block.
 pub enum ReadBlock {
     ElectionFinalized(ElectionFinalized),
@@ -52,30 +264,68 @@ pub enum ReadBlock {
     Done,
 }
 
+/// A solution submitted in a block that hasn't been matched with its `SolutionStored` event yet.
+struct PendingSubmission {
+    score: ElectionScore,
+    who: Address,
+    /// The round at the block the solution was submitted in.
+    round: u32,
+    extrinsic_index: u32,
+    extrinsic_hash: Hash,
+    /// The round of the snapshot the solution was computed against.
+    solution_round: u32,
+    /// The number of voters and winners in the solution, `None` if it couldn't be decoded.
+    solution_size: Option<(u32, u32)>,
+}
+
+impl PendingSubmission {
+    fn new(ext: &ExtrinsicDetails, who: Address, round: u32) -> anyhow::Result<Self> {
+        let (score, solution_round) = get_solution_score_and_round(ext)?;
+
+        Ok(Self {
+            score,
+            who,
+            round,
+            extrinsic_index: ext.index(),
+            extrinsic_hash: ext.hash(),
+            solution_round,
+            solution_size: get_solution_size_of(ext),
+        })
+    }
+}
+
 pub async fn read_block(
     client: &Client,
     block: &Header,
     state: &mut ElectionRound,
-    db: &db::Database,
+    db: &dyn Store,
 ) -> anyhow::Result<ReadBlock> {
     let mut res = ReadBlock::Done;
+    let parent_hash = block.parent_hash;
     let phase = get_phase(client, block.hash()).await?.0;
     let round = get_round(client, block.hash()).await?;
 
     tracing::trace!(
         target: LOG_TARGET,
-        "fetch block={}, phase={:?}, round={round}",
-        block.number(),
-        phase,
+        block = block.number(),
+        ?phase,
+        round,
+        "fetch"
     );
 
     if !phase.is_signed() && !phase.is_unsigned_open() && !state.waiting_for_election_finalized() {
         return Ok(ReadBlock::PhaseClosed);
     }
 
+    let created_at = to_date_time(get_timestamp(client, block.hash()).await?)?;
     let block = client.chain_api().blocks().at(block.hash()).await?;
     let mut submissions = HashMap::new();
 
+    // The signed queue is only fetched if a solution was ejected and then
+    // the signed solutions stored earlier in this block are replayed on top of it.
+    let mut signed_queue = None;
+    let mut stored_signed = Vec::new();
+
     let extrinsics = block.extrinsics().await?;
 
     for ext in extrinsics.iter() {
@@ -89,64 +339,162 @@ pub async fn read_block(
         tracing::debug!(target: LOG_TARGET, "extrinsic={}_{}, idx={}", pallet_name, call, ext.index());
 
         if call == "submit" {
-            // TODO: use multiaddress here instead of asserting the address is 33 bytes
-            let address = ext
-                .address_bytes()
-                .map(|b| Address::from_bytes(&b[1..]))
-                .ok_or_else(|| anyhow::anyhow!("EPM::submit must have an address"))?;
+            let address =
+                signer(&ext)?.ok_or_else(|| anyhow::anyhow!("EPM::submit must have an address"))?;
 
-            let score = get_solution_score(&ext)?;
-            submissions.insert(ext.index(), (score, address, round));
+            submissions.insert(ext.index(), PendingSubmission::new(&ext, address, round)?);
+            state.add_signed_submission();
         }
 
         if call == "submit_unsigned" {
-            let score = get_solution_score(&ext)?;
-            submissions.insert(ext.index(), (score, Address::unsigned(), round));
+            submissions.insert(
+                ext.index(),
+                PendingSubmission::new(&ext, Address::unsigned(), round)?,
+            );
         }
     }
 
+    // Only read for the blocks with submissions.
+    let spec_version = if submissions.is_empty() {
+        None
+    } else {
+        get_spec_version(client, block.hash()).await
+    };
+
+    // The names of the errors of the failed extrinsics by extrinsic index.
+    let mut failed_extrinsics = HashMap::new();
+
     for event in block.events().await?.iter() {
         let event = event?;
 
+        if let Some(failed) = event.as_event::<runtime::system::events::ExtrinsicFailed>()? {
+            if let subxt::events::Phase::ApplyExtrinsic(idx) = event.phase() {
+                failed_extrinsics.insert(idx, dispatch_error_name(client, &failed.dispatch_error));
+            }
+        }
+
         if event.pallet_name() != EPM_PALLET_NAME {
             continue;
         }
 
         tracing::debug!(target: LOG_TARGET, "event={}_{}", event.pallet_name(), event.variant_name());
 
-        if (event.as_event::<runtime::election_provider_multi_phase::events::SolutionStored>()?)
-            .is_some()
+        if let Some(stored) =
+            event.as_event::<runtime::election_provider_multi_phase::events::SolutionStored>()?
         {
-            if let subxt::events::Phase::ApplyExtrinsic(idx) = event.phase() {
-                if let Some((score, who, r)) = submissions.remove(&idx) {
-                    tracing::trace!(target: LOG_TARGET, "Solution submitted who={who},score={:?}", score);
-                    db.insert_submission(Submission::new(who, r, block.number(), score, true))
-                        .await?;
+            let tracked = match event.phase() {
+                subxt::events::Phase::ApplyExtrinsic(idx) => submissions.remove(&idx),
+                _ => None,
+            };
+
+            let Some(PendingSubmission {
+                score,
+                who,
+                round: r,
+                extrinsic_index,
+                extrinsic_hash,
+                solution_round,
+                solution_size,
+            }) = tracked
+            else {
+                // Solutions stored by governance are not submitted via `submit` or `submit_unsigned`.
+                if matches!(
+                    stored.compute,
+                    ElectionCompute::Signed | ElectionCompute::Unsigned
+                ) {
+                    tracing::warn!(
+                        target: LOG_TARGET,
+                        block = block.number(),
+                        phase = ?event.phase(),
+                        "SolutionStored has no matching submission"
+                    );
+                    prometheus::record_unmatched_solution_stored();
+                }
+                continue;
+            };
+
+            tracing::trace!(target: LOG_TARGET, "Solution submitted who={who},score={:?}", score);
+
+            // For unsigned solutions `prev_ejected` refers to the queued solution
+            // and not to the signed queue.
+            if stored.prev_ejected && stored.origin.is_some() {
+                let queue = match signed_queue.as_mut() {
+                    Some(queue) => queue,
+                    None => {
+                        let mut queue = SignedQueue::fetch(client, parent_hash).await?;
+                        for (score, who) in stored_signed.drain(..) {
+                            queue.insert(score, block.number(), who);
+                        }
+                        signed_queue.insert(queue)
+                    }
+                };
+
+                match queue.eject(score, block.number(), who.clone()) {
+                    Some(ejected) => {
+                        tracing::trace!(target: LOG_TARGET, "Solution ejected who={ejected},by={who}");
+                        db.insert_ejection(Ejection::new(ejected, who.clone(), r, block.number()))
+                            .await?;
+                    }
+                    None => {
+                        tracing::warn!(target: LOG_TARGET, block = block.number(), "Solution ejected but the signed queue was empty");
+                    }
+                }
+            } else if stored.origin.is_some() {
+                match signed_queue.as_mut() {
+                    Some(queue) => queue.insert(score, block.number(), who.clone()),
+                    None => stored_signed.push((score, who.clone())),
                 }
             }
+
+            prometheus::record_submission(&who, true);
+            db.insert_submission(
+                Submission::new(who, r, block.number(), score, true)
+                    .with_extrinsic(extrinsic_index, extrinsic_hash)
+                    .with_stale_snapshot(solution_round < r)
+                    .with_created_at(created_at)
+                    .with_spec_version(spec_version)
+                    .with_solution_size(solution_size),
+            )
+            .await?;
+        }
+
+        if let Some(transition) =
+            event.as_event::<runtime::election_provider_multi_phase::events::PhaseTransitioned>()?
+        {
+            db.insert_phase_transition(PhaseTransition::new(
+                transition.round,
+                block.number(),
+                phase_name(&transition.from),
+                phase_name(&transition.to),
+            ))
+            .await?;
         }
 
         if let Some(winner) =
             event.as_event::<runtime::election_provider_multi_phase::events::ElectionFinalized>()?
         {
+            if matches!(
+                winner.compute,
+                ElectionCompute::Fallback | ElectionCompute::Emergency
+            ) {
+                state.governance_fallback();
+            }
             res = ReadBlock::ElectionFinalized(winner);
         }
 
         if let Some(rewarded) =
             event.as_event::<runtime::election_provider_multi_phase::events::Rewarded>()?
         {
-            state.set_winner(Address::from_bytes(rewarded.account.0.as_slice()));
+            state.add_winner(Address::from_bytes(rewarded.account.0.as_slice()));
         }
 
         if let Some(slashed) =
             event.as_event::<runtime::election_provider_multi_phase::events::Slashed>()?
         {
-            db.insert_slashed(Slashed::new(
-                slashed.account,
-                round,
-                block.number(),
-                slashed.value,
-            ))
+            db.insert_slashed(
+                Slashed::new(slashed.account, round, block.number(), slashed.value)
+                    .with_created_at(created_at),
+            )
             .await?;
         }
 
@@ -158,13 +506,142 @@ pub async fn read_block(
         }
     }
 
-    for (_, missed) in submissions.into_iter() {
-        let (score, who, r) = missed;
-        db.insert_submission(Submission::new(who, r, block.number(), score, false))
+    insert_missed_submissions(
+        db,
+        submissions,
+        &failed_extrinsics,
+        &phase,
+        block.number(),
+        created_at,
+        spec_version,
+    )
+    .await?;
+
+    Ok(res)
+}
+
+/// Insert the submissions of `block` that weren't stored as failed, the reason is the error
+/// of their extrinsic in `failed_extrinsics` if it failed and otherwise derived from `phase`.
+async fn insert_missed_submissions(
+    db: &dyn Store,
+    missed: HashMap<u32, PendingSubmission>,
+    failed_extrinsics: &HashMap<u32, Option<String>>,
+    phase: &Phase<u32>,
+    block: u32,
+    created_at: OffsetDateTime,
+    spec_version: Option<u32>,
+) -> anyhow::Result<()> {
+    for (_, missed) in missed {
+        let PendingSubmission {
+            score,
+            who,
+            round: r,
+            extrinsic_index,
+            extrinsic_hash,
+            solution_round,
+            solution_size,
+        } = missed;
+
+        let reason = match failed_extrinsics.get(&extrinsic_index) {
+            Some(Some(error)) => FailureReason::from_error(error),
+            _ if solution_round < r => FailureReason::StaleSnapshot,
+            _ if who == Address::unsigned() && !phase.is_unsigned_open() => {
+                FailureReason::WrongPhase
+            }
+            _ if who != Address::unsigned() && !phase.is_signed() => FailureReason::WrongPhase,
+            _ => FailureReason::Invalid,
+        };
+
+        prometheus::record_submission(&who, false);
+        prometheus::record_failed_submission(&who, reason);
+        db.insert_submission(
+            Submission::new(who, r, block, score, false)
+                .with_extrinsic(extrinsic_index, extrinsic_hash)
+                .with_stale_snapshot(solution_round < r)
+                .with_failure_reason(reason)
+                .with_created_at(created_at)
+                .with_spec_version(spec_version)
+                .with_solution_size(solution_size),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Record the extrinsics of the block that match one of `calls`, given as `Pallet::call`.
+///
+/// Only top-level extrinsics are matched, calls that are dispatched by other calls
+/// such as `Sudo::sudo` or an enacted referendum aren't recorded.
+pub async fn read_config_changes(
+    client: &Client,
+    db: &dyn Store,
+    block: &Header,
+    calls: &[String],
+) -> anyhow::Result<()> {
+    let round = get_round(client, block.hash()).await?;
+    let extrinsics = client
+        .chain_api()
+        .blocks()
+        .at(block.hash())
+        .await?
+        .extrinsics()
+        .await?;
+
+    for ext in extrinsics.iter() {
+        let call = format!("{}::{}", ext.pallet_name()?, ext.variant_name()?);
+
+        if !calls.contains(&call) {
+            continue;
+        }
+
+        let signer = signer(&ext).unwrap_or_else(|e| {
+            tracing::warn!(target: LOG_TARGET, "Unknown signer of call={call}: {e}");
+            None
+        });
+        let args = ext.field_values()?.to_string();
+
+        tracing::info!(target: LOG_TARGET, call, block = block.number(), round, "Config change");
+
+        db.insert_config_change(ConfigChange::new(call, signer, round, block.number(), args))
             .await?;
     }
 
-    Ok(res)
+    Ok(())
+}
+
+/// Get the signer of a signed extrinsic, `None` if the extrinsic isn't signed.
+fn signer(ext: &ExtrinsicDetails) -> anyhow::Result<Option<Address>> {
+    let Some(bytes) = ext.address_bytes() else {
+        return Ok(None);
+    };
+
+    address_from_multiaddress_bytes(bytes)
+        .map(Some)
+        .map_err(|e| anyhow::anyhow!("Extrinsic {}: {e}", ext.index()))
+}
+
+/// Decode the SCALE encoded `MultiAddress` of a signer.
+///
+/// Fails for the variants that aren't a 32 byte account, such as an account index,
+/// which would need to be looked up in the `Indices` pallet.
+fn address_from_multiaddress_bytes(mut bytes: &[u8]) -> anyhow::Result<Address> {
+    let address = <MultiAddress<AccountId32, u32> as codec::DecodeAll>::decode_all(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Invalid signer address: {e}"))?;
+
+    match address {
+        MultiAddress::Id(id) => Ok(Address::from_bytes(id.0.as_ref())),
+        MultiAddress::Address32(bytes) => Ok(Address::from_bytes(&bytes)),
+        MultiAddress::Raw(bytes) if bytes.len() == 32 => Ok(Address::from_bytes(&bytes)),
+        MultiAddress::Raw(bytes) => Err(anyhow::anyhow!(
+            "Unsupported signer: raw address of {} bytes",
+            bytes.len()
+        )),
+        MultiAddress::Index(index) => {
+            Err(anyhow::anyhow!("Unsupported signer: account index {index}"))
+        }
+        MultiAddress::Address20(_) => Err(anyhow::anyhow!("Unsupported signer: 20 byte address")),
+    }
 }
 
 pub async fn get_block(client: &Client, n: u64) -> anyhow::Result<Header> {
@@ -185,8 +662,165 @@ pub async fn get_block(client: &Client, n: u64) -> anyhow::Result<Header> {
     Ok(header)
 }
 
-/// Runs until the RPC connection fails or updating the metadata failed.
-pub async fn runtime_upgrade_task(client: ChainClient, tx: mpsc::Sender<String>) {
+/// Where to read the finalized blocks from.
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum BlockSource {
+    /// Subscribe to the finalized blocks.
+    Subscribe,
+    /// Periodically poll the finalized head, for RPC nodes that don't support subscriptions.
+    Poll,
+}
+
+/// Which blocks to follow.
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum Follow {
+    /// Follow the finalized blocks.
+    Finalized,
+    /// Follow the best blocks, which are seen earlier but may be reverted by a reorg.
+    Best,
+}
+
+pub type FinalizedBlocks = StreamOfResults<(Header, BlockRef<Hash>)>;
+
+/// Get a stream of the finalized or best blocks from the given block source.
+///
+/// The `poll_interval` is only used for [`BlockSource::Poll`].
+pub async fn finalized_blocks(
+    client: &Client,
+    source: BlockSource,
+    follow: Follow,
+    poll_interval: Duration,
+) -> anyhow::Result<FinalizedBlocks> {
+    let backend = client.chain_api().backend();
+    match (source, follow) {
+        (BlockSource::Subscribe, Follow::Finalized) => {
+            Ok(backend.stream_finalized_block_headers().await?)
+        }
+        (BlockSource::Subscribe, Follow::Best) => Ok(backend.stream_best_block_headers().await?),
+        (BlockSource::Poll, follow) => Ok(poll_finalized_blocks(
+            client.rpc().clone(),
+            follow,
+            poll_interval,
+        )),
+    }
+}
+
+struct PollState {
+    rpc: RpcClient,
+    follow: Follow,
+    interval: Duration,
+    /// The next block to emit, `None` until the finalized head has been fetched once.
+    next: Option<u64>,
+    finalized: u64,
+}
+
+impl PollState {
+    /// The number of finalized blocks that haven't been emitted yet.
+    fn backlog(&self) -> u64 {
+        self.next
+            .map_or(0, |n| (self.finalized + 1).saturating_sub(n))
+    }
+}
+
+/// Polls `chain_getFinalizedHead` (or the best head with [`Follow::Best`]) every `interval`
+/// and emits all blocks that were added since the last poll in order.
+fn poll_finalized_blocks(rpc: RpcClient, follow: Follow, interval: Duration) -> FinalizedBlocks {
+    let state = PollState {
+        rpc,
+        follow,
+        interval,
+        next: None,
+        finalized: 0,
+    };
+
+    let stream = futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(n) = state.next.filter(|n| *n <= state.finalized) {
+                let res = get_block_by_number(&state.rpc, n).await;
+                if res.is_ok() {
+                    state.next = Some(n + 1);
+                    prometheus::record_unprocessed_block_backlog(state.backlog());
+                }
+                return Some((res, state));
+            }
+
+            if state.next.is_some() {
+                tokio::time::sleep(state.interval).await;
+            }
+
+            let head = match state.follow {
+                Follow::Finalized => get_finalized_number(&state.rpc).await,
+                Follow::Best => get_best_number(&state.rpc).await,
+            };
+            let finalized = match head {
+                Ok(n) => n,
+                Err(e) => return Some((Err(e), state)),
+            };
+            state.finalized = finalized;
+            state.next.get_or_insert(finalized);
+            prometheus::record_unprocessed_block_backlog(state.backlog());
+        }
+    });
+
+    StreamOf::new(Box::pin(stream))
+}
+
+pub async fn get_finalized_number(rpc: &RpcClient) -> Result<u64, subxt::Error> {
+    let hash = rpc.chain_get_finalized_head().await?;
+    let header = rpc
+        .chain_get_header(Some(hash))
+        .await?
+        .ok_or_else(|| subxt::Error::Other(format!("Finalized block {hash:?} not found")))?;
+
+    Ok(header.number as u64)
+}
+
+pub async fn get_best_number(rpc: &RpcClient) -> Result<u64, subxt::Error> {
+    let header = rpc
+        .chain_get_header(None)
+        .await?
+        .ok_or_else(|| subxt::Error::Other("Best block not found".to_string()))?;
+
+    Ok(header.number as u64)
+}
+
+async fn get_block_by_number(
+    rpc: &RpcClient,
+    n: u64,
+) -> Result<(Header, BlockRef<Hash>), subxt::Error> {
+    let hash = rpc
+        .chain_get_block_hash(Some(n.into()))
+        .await?
+        .ok_or_else(|| subxt::Error::Other(format!("Finalized block {n} not found")))?;
+    let header = rpc
+        .chain_get_header(Some(hash))
+        .await?
+        .ok_or_else(|| subxt::Error::Other(format!("Finalized block {hash:?} not found")))?;
+
+    Ok((header, BlockRef::from_hash(hash)))
+}
+
+/// What to do when the metadata couldn't be updated after a runtime upgrade.
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum MetadataUpdateFailure {
+    /// Log the error and continue with the old metadata.
+    Keep,
+    /// Shut down the monitor.
+    Halt,
+    /// Log an error, mark the metadata as stale in prometheus and continue with the old metadata.
+    Alert,
+}
+
+/// Apply the metadata of runtime upgrades and send the new spec version of each upgrade to `upgrades`.
+///
+/// Runs until the RPC connection fails or updating the metadata failed
+/// and `on_failure` is [`MetadataUpdateFailure::Halt`].
+pub async fn runtime_upgrade_task(
+    client: ChainClient,
+    tx: mpsc::Sender<String>,
+    upgrades: watch::Sender<Option<u32>>,
+    on_failure: MetadataUpdateFailure,
+) {
     let updater = client.updater();
 
     let mut update_stream = match updater.runtime_updates().await {
@@ -215,23 +849,145 @@ pub async fn runtime_upgrade_task(client: ChainClient, tx: mpsc::Sender<String>)
 
         let version = update.runtime_version().spec_version;
 
-        match updater.apply_update(update) {
+        let res = updater.apply_update(update);
+        // The subscription starts with the current runtime version, which isn't an upgrade.
+        if !matches!(res, Err(UpgradeError::SameVersion)) {
+            prometheus::record_runtime_upgrade(version);
+            upgrades.send_replace(Some(version));
+        }
+
+        match res {
             Ok(()) => {
                 tracing::info!(target: LOG_TARGET, "upgrade to version: {} successful", version);
+                prometheus::record_metadata_stale(false);
+            }
+            Err(e) => match on_failure {
+                MetadataUpdateFailure::Keep => {
+                    tracing::debug!(target: LOG_TARGET, "upgrade to version: {} failed: {:?}", version, e);
+                }
+                MetadataUpdateFailure::Halt => {
+                    _ = tx
+                        .send(format!("upgrade to version: {version} failed: {e:?}"))
+                        .await;
+                    return;
+                }
+                MetadataUpdateFailure::Alert => {
+                    tracing::error!(target: LOG_TARGET, "upgrade to version: {} failed, metadata may be stale: {:?}", version, e);
+                    prometheus::record_metadata_stale(true);
+                }
+            },
+        }
+    }
+}
+
+/// Periodically deletes all data older than the most recent `rounds` rounds.
+pub async fn retention_task(db: db::Database, rounds: u32, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        let latest = match db.get_latest_round().await {
+            Ok(Some(latest)) => latest,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::warn!(target: LOG_TARGET, "retention: failed to get the latest round: {e}");
+                continue;
+            }
+        };
+
+        let Some(oldest) = latest.checked_sub(rounds) else {
+            continue;
+        };
+
+        match db.prune_before_round(oldest).await {
+            Ok(deleted) => {
+                for (table, n) in deleted.into_iter().filter(|(_, n)| *n > 0) {
+                    tracing::info!(target: LOG_TARGET, rows = n, round = oldest, table, "retention: deleted the rows older than round");
+                }
             }
             Err(e) => {
-                tracing::debug!(target: LOG_TARGET, "upgrade to version: {} failed: {:?}", version, e);
+                tracing::warn!(target: LOG_TARGET, "retention: failed to prune rounds older than {oldest}: {e}");
             }
         }
     }
 }
 
+/// How to count the database rows for the `<prefix>_db_rows` gauge.
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum RowCount {
+    /// Use `COUNT(*)`, which is slow for large tables.
+    Exact,
+    /// Use the estimate of the query planner.
+    Estimate,
+}
+
+/// Periodically records the number of rows in each table in prometheus.
+pub async fn db_rows_task(db: db::Database, count: RowCount, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        for table in db::TABLES {
+            match db.count_rows(table, count == RowCount::Estimate).await {
+                Ok(rows) => prometheus::record_db_rows(table, rows),
+                Err(e) => {
+                    tracing::warn!(target: LOG_TARGET, "failed to count the rows of {table}: {e}");
+                }
+            }
+        }
+    }
+}
+
+/// Periodically check that the recently processed blocks are contiguous
+/// and send the missing blocks to be processed.
+pub async fn block_gaps_task(tx: mpsc::Sender<Vec<u32>>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        let missing = ingestion::take_missing_blocks();
+        if missing.is_empty() {
+            continue;
+        }
+
+        tracing::warn!(target: LOG_TARGET, "Detected {} skipped blocks: {missing:?}", missing.len());
+        prometheus::record_block_gaps(missing.len() as u64);
+
+        if tx.send(missing).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Delete the rows read from the block that was processed with the hash `prev` before `block`
+/// replaced it in a reorg, `block` is then read instead.
+pub async fn delete_reverted_block(
+    db: &dyn Store,
+    block: &Header,
+    prev: Hash,
+) -> anyhow::Result<()> {
+    prometheus::record_reorg();
+    let deleted = db.delete_block(block.number()).await?;
+    tracing::warn!(
+        target: LOG_TARGET,
+        block = block.number(),
+        ?prev,
+        hash = ?block.hash(),
+        deleted,
+        "The block was processed before with another hash, the chain was reorganized, reading it again"
+    );
+    Ok(())
+}
+
 // Read the previous blocks in the current round.
 pub async fn read_remaining_blocks_in_round(
     client: &Client,
     state: &mut ElectionRound,
     block_num: u64,
-    db: &db::Database,
+    db: &dyn Store,
 ) -> anyhow::Result<()> {
     let first_block = std::cmp::min(
         block_num,
@@ -243,6 +999,16 @@ pub async fn read_remaining_blocks_in_round(
     let mut prev_block = first_block.checked_sub(1);
     while let Some(b) = prev_block {
         let old_block = get_block(client, b).await?;
+        match state.hash(b) {
+            // Already read, e.g. a skipped block that was processed later.
+            Some(hash) if hash == old_block.hash() => {
+                prev_block = b.checked_sub(1);
+                continue;
+            }
+            Some(prev) => delete_reverted_block(db, &old_block, prev).await?,
+            None => {}
+        }
+
         match read_block(client, &old_block, state, db).await? {
             ReadBlock::PhaseClosed | ReadBlock::ElectionFinalized(_) => break,
             ReadBlock::Done => {}
@@ -253,19 +1019,143 @@ pub async fn read_remaining_blocks_in_round(
     Ok(())
 }
 
-fn get_solution_score(ext: &ExtrinsicDetails) -> Result<ElectionScore, anyhow::Error> {
+/// The expected time between two blocks, used to wait for blocks to be finalized.
+const BLOCK_TIME: Duration = Duration::from_secs(6);
+
+/// Read the `Rewarded` events of the `blocks` blocks after `block_num` in which the election
+/// was finalized, waiting for them to be finalized first, and return the rewarded accounts.
+pub async fn read_late_rewards(
+    client: &Client,
+    block_num: u64,
+    blocks: u32,
+) -> anyhow::Result<Vec<Address>> {
+    let last = block_num + blocks as u64;
+
+    while get_finalized_number(client.rpc()).await? < last {
+        tokio::time::sleep(BLOCK_TIME).await;
+    }
+
+    let mut winners = Vec::new();
+    for n in block_num + 1..=last {
+        let block = get_block(client, n).await?;
+
+        for event in client.chain_api().events().at(block.hash()).await?.iter() {
+            let event = event?;
+
+            if let Some(rewarded) =
+                event.as_event::<runtime::election_provider_multi_phase::events::Rewarded>()?
+            {
+                tracing::info!(target: LOG_TARGET, block = n, finalized_at = block_num, "Late reward for the election");
+                winners.push(Address::from_bytes(rewarded.account.0.as_slice()));
+            }
+        }
+    }
+
+    Ok(winners)
+}
+
+/// The name of the pallet error of a failed extrinsic, `None` if it didn't fail with a pallet error.
+fn dispatch_error_name(client: &Client, error: &DispatchError) -> Option<String> {
+    let DispatchError::Module(ModuleError { index, error }) = error else {
+        return None;
+    };
+    let metadata = client.chain_api().metadata();
+    let variant = metadata
+        .pallet_by_index(*index)?
+        .error_variant_by_index(error[0])?;
+
+    Some(variant.name.clone())
+}
+
+fn phase_name(phase: &EpmPhase) -> &'static str {
+    match phase.0 {
+        Phase::Off => "off",
+        Phase::Signed => "signed",
+        Phase::Unsigned(_) => "unsigned",
+        Phase::Emergency => "emergency",
+    }
+}
+
+fn get_solution_score_and_round(
+    ext: &ExtrinsicDetails,
+) -> Result<(ElectionScore, u32), anyhow::Error> {
     let scale_val = ext.field_values()?;
+    let raw_solution = scale_val
+        .at("raw_solution")
+        .ok_or_else(|| anyhow::anyhow!("RawSolution not found"))?;
 
     let score = {
-        let val = scale_val
-            .at("raw_solution")
-            .ok_or_else(|| anyhow::anyhow!("RawSolution not found"))?
+        let val = raw_solution
             .at("score")
             .ok_or_else(|| anyhow::anyhow!("RawSolution::score not found"))?;
         decode_scale_val(val)?
     };
 
-    Ok(score)
+    let round = raw_solution
+        .at("round")
+        .and_then(|v| v.as_u128())
+        .ok_or_else(|| anyhow::anyhow!("RawSolution::round not found"))?;
+
+    Ok((score, round as u32))
+}
+
+/// Get the number of voters and winners of the solution submitted by `ext`.
+fn get_solution_size_of(ext: &ExtrinsicDetails) -> Option<(u32, u32)> {
+    let size = ext.field_values().ok().and_then(|val| {
+        val.at("raw_solution")
+            .and_then(|raw_solution| raw_solution.at("solution"))
+            .and_then(get_solution_size)
+    });
+
+    if size.is_none() {
+        tracing::warn!(
+            target: LOG_TARGET,
+            "Failed to decode the solution size of extrinsic {}",
+            ext.index()
+        );
+    }
+
+    size
+}
+
+/// Count the voters and the distinct winners of a compact solution.
+///
+/// The solution has a `votesN` field for voters with `N` targets, where each vote is
+/// `(voter, target)` for `votes1` and `(voter, [(target, weight); N - 1], target)` otherwise.
+fn get_solution_size<Ctx>(solution: &Value<Ctx>) -> Option<(u32, u32)> {
+    let ValueDef::Composite(votes) = &solution.value else {
+        return None;
+    };
+
+    let mut voters = 0;
+    let mut winners = HashSet::new();
+    for votes in votes.values() {
+        let ValueDef::Composite(votes) = &votes.value else {
+            return None;
+        };
+        voters += votes.len() as u32;
+
+        for vote in votes.values() {
+            let ValueDef::Composite(vote) = &vote.value else {
+                return None;
+            };
+            // Skip the voter index.
+            for target in vote.values().skip(1) {
+                match &target.value {
+                    ValueDef::Composite(weighted) => {
+                        for pair in weighted.values() {
+                            winners.insert(pair.at(0)?.as_u128()?);
+                        }
+                    }
+                    _ => {
+                        winners.insert(target.as_u128()?);
+                    }
+                }
+            }
+        }
+    }
+
+    Some((voters, winners.len() as u32))
 }
 
 fn make_type<T: TypeInfo + 'static>() -> (u32, PortableRegistry) {
@@ -286,3 +1176,161 @@ where
     let bytes = val.encode_as_type(ty_id, &types)?;
     Decode::decode(&mut bytes.as_ref()).map_err(Into::into)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory::MemoryStore;
+    use codec::Encode;
+
+    fn decode(address: MultiAddress<AccountId32, u32>) -> anyhow::Result<Address> {
+        address_from_multiaddress_bytes(&address.encode())
+    }
+
+    fn account() -> Address {
+        Address::from_bytes(&[7; 32])
+    }
+
+    #[test]
+    fn signer_id() {
+        let address = decode(MultiAddress::Id(AccountId32([7; 32]))).unwrap();
+        assert_eq!(address, account());
+    }
+
+    #[test]
+    fn signer_address32() {
+        let address = decode(MultiAddress::Address32([7; 32])).unwrap();
+        assert_eq!(address, account());
+    }
+
+    #[test]
+    fn signer_raw_32_bytes() {
+        let address = decode(MultiAddress::Raw(vec![7; 32])).unwrap();
+        assert_eq!(address, account());
+    }
+
+    #[test]
+    fn signer_raw_other_length() {
+        let err = decode(MultiAddress::Raw(vec![7; 20])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported signer: raw address of 20 bytes"
+        );
+    }
+
+    #[test]
+    fn signer_index() {
+        let err = decode(MultiAddress::Index(42)).unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported signer: account index 42");
+    }
+
+    #[test]
+    fn signer_address20() {
+        let err = decode(MultiAddress::Address20([7; 20])).unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported signer: 20 byte address");
+    }
+
+    #[test]
+    fn signer_invalid_bytes() {
+        let mut bytes = MultiAddress::<AccountId32, u32>::Id(AccountId32([7; 32])).encode();
+        bytes.pop();
+        assert!(address_from_multiaddress_bytes(&bytes).is_err());
+
+        // Trailing bytes aren't ignored.
+        let mut bytes = MultiAddress::<AccountId32, u32>::Id(AccountId32([7; 32])).encode();
+        bytes.push(0);
+        assert!(address_from_multiaddress_bytes(&bytes).is_err());
+    }
+
+    fn pending(who: Address, extrinsic_index: u32, solution_round: u32) -> PendingSubmission {
+        PendingSubmission {
+            score: ElectionScore::default(),
+            who,
+            round: 5,
+            extrinsic_index,
+            extrinsic_hash: Hash::from_low_u64_be(extrinsic_index as u64),
+            solution_round,
+            solution_size: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn missed_submissions_are_inserted_as_failed() {
+        let db = MemoryStore::default();
+        let missed = HashMap::from([
+            (1, pending(account(), 1, 5)),
+            (2, pending(account(), 2, 4)),
+            (3, pending(Address::unsigned(), 3, 5)),
+            (4, pending(account(), 4, 5)),
+        ]);
+        let failed_extrinsics = HashMap::from([(1, Some("SignedQueueFull".to_string()))]);
+
+        insert_missed_submissions(
+            &db,
+            missed,
+            &failed_extrinsics,
+            &Phase::Signed,
+            100,
+            OffsetDateTime::UNIX_EPOCH,
+            Some(1),
+        )
+        .await
+        .unwrap();
+
+        let mut reasons: Vec<_> = db
+            .submissions()
+            .iter()
+            .map(|s| {
+                let s = serde_json::to_value(s).unwrap();
+                assert_eq!(s["success"], false);
+                assert_eq!(s["block"], 100);
+                (s["extrinsic_index"].clone(), s["failure_reason"].clone())
+            })
+            .collect();
+        reasons.sort_by_key(|(index, _)| index.as_u64());
+
+        assert_eq!(
+            reasons,
+            [
+                (1.into(), "queue_full".into()),
+                (2.into(), "stale_snapshot".into()),
+                (3.into(), "wrong_phase".into()),
+                (4.into(), "invalid".into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reverted_block_rows_are_deleted() {
+        let db = MemoryStore::default();
+        for block in [100, 101] {
+            db.insert_submission(Submission::new(
+                account(),
+                5,
+                block,
+                ElectionScore::default(),
+                true,
+            ))
+            .await
+            .unwrap();
+        }
+        let block = Header {
+            parent_hash: Hash::zero(),
+            number: 100,
+            state_root: Hash::zero(),
+            extrinsics_root: Hash::zero(),
+            digest: Default::default(),
+        };
+
+        delete_reverted_block(&db, &block, Hash::from_low_u64_be(1))
+            .await
+            .unwrap();
+
+        let blocks: Vec<_> = db
+            .submissions()
+            .iter()
+            .map(|s| serde_json::to_value(s).unwrap()["block"].clone())
+            .collect();
+        assert_eq!(blocks, [101]);
+    }
+}
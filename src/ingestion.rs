@@ -0,0 +1,175 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use oasgen::OaSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The window used to compute the number of blocks processed per minute.
+const BLOCKS_PER_MINUTE_WINDOW: Duration = Duration::from_secs(60);
+
+/// The number of most recent processed blocks that are checked for gaps.
+const GAP_WINDOW: usize = 600;
+
+static INGESTION: Mutex<Ingestion> = Mutex::new(Ingestion {
+    finalized_head: None,
+    last_processed: None,
+    last_processed_timestamp: None,
+    processed: VecDeque::new(),
+    recent_blocks: BTreeSet::new(),
+    db_inserts: 0,
+    db_insert_time: Duration::ZERO,
+});
+
+struct Ingestion {
+    finalized_head: Option<u32>,
+    last_processed: Option<(u32, Instant)>,
+    /// The on-chain timestamp of the most recent processed block in milliseconds.
+    last_processed_timestamp: Option<u64>,
+    /// When the blocks within the last [`BLOCKS_PER_MINUTE_WINDOW`] were processed.
+    processed: VecDeque<Instant>,
+    /// The numbers of the most recent [`GAP_WINDOW`] processed blocks.
+    recent_blocks: BTreeSet<u32>,
+    db_inserts: u64,
+    db_insert_time: Duration,
+}
+
+/// Summary of the block processing performance.
+#[derive(Debug, Clone, Serialize, Deserialize, OaSchema)]
+pub struct Latency {
+    /// The most recent finalized block seen.
+    finalized_head: Option<u32>,
+    /// The most recent block that was processed.
+    last_processed_block: Option<u32>,
+    /// The number of finalized blocks that haven't been processed yet.
+    lag_blocks: u32,
+    /// The number of seconds between the on-chain timestamp of the most recent processed block and now.
+    lag_secs: Option<u64>,
+    /// The number of seconds since the most recent block was processed.
+    secs_since_last_block: Option<u64>,
+    /// The number of blocks processed in the last minute.
+    blocks_per_minute: u64,
+    /// The average time to insert a row into the database in milliseconds.
+    avg_db_insert_ms: f64,
+}
+
+fn ingestion() -> std::sync::MutexGuard<'static, Ingestion> {
+    // The lock is never held across a panic.
+    INGESTION.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Record the most recent finalized block seen.
+pub fn record_finalized_head(block: u32) {
+    ingestion().finalized_head = Some(block);
+}
+
+/// Record the on-chain timestamp in milliseconds of the block that is being processed.
+///
+/// Older blocks that are processed later, e.g. to fill a gap, don't move the timestamp back.
+pub fn record_block_timestamp(timestamp: u64) {
+    let mut ingestion = ingestion();
+    ingestion.last_processed_timestamp = ingestion.last_processed_timestamp.max(Some(timestamp));
+}
+
+/// Record that a block has been processed.
+pub fn record_processed_block(block: u32) {
+    let now = Instant::now();
+    let mut ingestion = ingestion();
+
+    if ingestion
+        .last_processed
+        .is_none_or(|(last, _)| block >= last)
+    {
+        ingestion.last_processed = Some((block, now));
+    }
+    ingestion.recent_blocks.insert(block);
+    while ingestion.recent_blocks.len() > GAP_WINDOW {
+        ingestion.recent_blocks.pop_first();
+    }
+    ingestion.processed.push_back(now);
+    while ingestion
+        .processed
+        .front()
+        .is_some_and(|t| now.duration_since(*t) > BLOCKS_PER_MINUTE_WINDOW)
+    {
+        ingestion.processed.pop_front();
+    }
+}
+
+/// Returns the blocks between the oldest and the most recent processed block of the
+/// window that weren't processed and records them as processed, so they are only returned once.
+pub fn take_missing_blocks() -> Vec<u32> {
+    let mut ingestion = ingestion();
+    let (Some(first), Some(last)) = (
+        ingestion.recent_blocks.first().copied(),
+        ingestion.recent_blocks.last().copied(),
+    ) else {
+        return Vec::new();
+    };
+
+    let missing: Vec<u32> = (first..last)
+        .filter(|n| !ingestion.recent_blocks.contains(n))
+        .collect();
+    ingestion.recent_blocks.extend(missing.iter().copied());
+
+    missing
+}
+
+/// Record the time it took to insert a row into the database.
+pub fn record_db_insert(elapsed: Duration) {
+    let mut ingestion = ingestion();
+    ingestion.db_inserts += 1;
+    ingestion.db_insert_time += elapsed;
+}
+
+/// How far the most recent processed block is behind the wall clock,
+/// `None` if no block has been processed yet.
+pub fn lag() -> Option<Duration> {
+    let timestamp = ingestion().last_processed_timestamp;
+    lag_since(timestamp?)
+}
+
+fn lag_since(timestamp: u64) -> Option<Duration> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    Some(now.saturating_sub(Duration::from_millis(timestamp)))
+}
+
+pub fn latency() -> Latency {
+    let now = Instant::now();
+    let ingestion = ingestion();
+    let last_processed_block = ingestion.last_processed.map(|(n, _)| n);
+
+    let lag_blocks = match (ingestion.finalized_head, last_processed_block) {
+        (Some(head), Some(last)) => head.saturating_sub(last),
+        (Some(head), None) => head,
+        _ => 0,
+    };
+
+    let avg_db_insert_ms = if ingestion.db_inserts == 0 {
+        0.0
+    } else {
+        ingestion.db_insert_time.as_secs_f64() * 1000.0 / ingestion.db_inserts as f64
+    };
+
+    Latency {
+        finalized_head: ingestion.finalized_head,
+        last_processed_block,
+        lag_blocks,
+        lag_secs: ingestion
+            .last_processed_timestamp
+            .and_then(lag_since)
+            .map(|l| l.as_secs()),
+        secs_since_last_block: ingestion
+            .last_processed
+            .map(|(_, t)| now.duration_since(t).as_secs()),
+        blocks_per_minute: ingestion
+            .processed
+            .iter()
+            .filter(|t| now.duration_since(**t) <= BLOCKS_PER_MINUTE_WINDOW)
+            .count() as u64,
+        avg_db_insert_ms,
+    }
+}
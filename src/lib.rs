@@ -0,0 +1,120 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Follows the elections of a chain with the election provider multi phase pallet and records
+//! them in a PostgreSQL database, which is served by a REST API.
+
+pub mod backfill;
+pub mod config;
+pub mod db;
+pub mod events;
+pub mod export;
+pub mod helpers;
+pub mod ingestion;
+pub mod monitor;
+pub mod prometheus;
+pub mod routes;
+pub mod rpc;
+pub mod tls;
+pub mod types;
+pub mod verify;
+
+use std::sync::Arc;
+
+use url::Url;
+
+pub use types::Address;
+
+pub const LOG_TARGET: &str = "polkadot-staking-miner-monitor";
+
+/// Returns the URL with the password replaced, suitable for logging.
+pub fn redact_password(url: &Url) -> Url {
+    let mut url = url.clone();
+    if url.password().is_some() {
+        // Only fails for URLs that can't have credentials, which isn't the case here.
+        _ = url.set_password(Some("***"));
+    }
+    url
+}
+
+/// Build the REST API router together with its OpenAPI spec, `/metrics` is only included with `with_metrics`.
+pub fn api(with_metrics: bool) -> (axum::Router<routes::AppState>, Arc<oasgen::OpenAPI>) {
+    let mut server = oasgen::Server::axum()
+        .route_json_spec("/docs/openapi.json")
+        .route_yaml_spec("/docs/openapi.yaml")
+        .swagger_ui("/docs/")
+        .get("/elections/", routes::all_elections)
+        .get("/elections/unsigned", routes::all_unsigned_elections)
+        .get("/elections/failed", routes::all_failed_elections)
+        .get("/elections/signed", routes::all_signed_elections)
+        .get(
+            "/elections/unsigned-despite-signed",
+            routes::unsigned_despite_signed_elections,
+        )
+        .get(
+            "/elections/with-submissions",
+            routes::elections_with_submissions,
+        )
+        .get("/elections/count", routes::count_elections)
+        .get("/elections/{n}", routes::most_recent_elections)
+        .get("/elections/{round}/winners", routes::election_winners)
+        .get("/round/{round}", routes::round)
+        .get("/slashed/", routes::all_slashed)
+        .get("/slashed/count", routes::count_slashed)
+        .get("/slashed/{n}", routes::most_recent_slashed)
+        .get("/ejections/", routes::all_ejections)
+        .get("/config-changes/", routes::all_config_changes)
+        .get("/submissions/", routes::all_submissions)
+        .get("/submissions/success", routes::all_success_submissions)
+        .get("/submissions/failed", routes::all_failed_submissions)
+        .get("/submissions/snipes", routes::snipes)
+        .get("/submissions/weak", routes::weak_submissions)
+        .get(
+            "/submissions/by-extrinsic/{hash}",
+            routes::submission_by_extrinsic,
+        )
+        .post(
+            "/submissions/by-addresses",
+            routes::submissions_by_addresses,
+        )
+        .get("/submissions/by/{address}/win-rate", routes::win_rate)
+        .get("/submissions/count", routes::count_submissions)
+        .get("/submissions/{n}", routes::most_recent_submissions)
+        .get("/miners", routes::miners)
+        .get("/miners/never-successful", routes::never_successful_miners)
+        .get("/miners/{address}", routes::miner)
+        .get("/current/best-score", routes::best_score)
+        .get("/health", routes::health)
+        .get("/ready", routes::ready)
+        .get("/stats", routes::stats)
+        .get("/stats/miner-distribution", routes::miner_distribution)
+        .get("/stats/latency", routes::latency);
+    if with_metrics {
+        server = server.get("/metrics", routes::metrics);
+    }
+    routes::document_errors(&mut server.openapi);
+    let server = server.freeze();
+    let spec = server.openapi.clone();
+
+    let router = server
+        .into_router()
+        .layer(axum::middleware::from_fn(routes::etag))
+        .route("/events/stream", axum::routing::get(routes::events_stream))
+        .route("/elections.csv", axum::routing::get(routes::elections_csv))
+        .route(
+            "/submissions.csv",
+            axum::routing::get(routes::submissions_csv),
+        )
+        .route(
+            "/stream/submissions",
+            axum::routing::get(routes::submissions_stream),
+        )
+        .route(
+            "/stream/elections",
+            axum::routing::get(routes::elections_stream),
+        )
+        .route_layer(axum::middleware::from_fn(routes::http_metrics));
+
+    (router, spec)
+}
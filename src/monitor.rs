@@ -0,0 +1,817 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::db::{Database, Election, Store};
+use crate::helpers::{
+    block_gaps_task, db_rows_task, delete_reverted_block, finalized_blocks, get_block,
+    get_block_author, get_elected, get_election_targets, get_finalized_number, get_phase,
+    get_round, get_snapshot_size, get_spec_version, get_timestamp, read_block, read_config_changes,
+    read_late_rewards, read_remaining_blocks_in_round, retention_task, runtime_upgrade_task,
+    to_date_time, BlockSource, Follow, MetadataUpdateFailure, ReadBlock, RowCount,
+};
+use crate::routes::{self, ApiAuth, ApiKey, AppState};
+use crate::types::{Client, ElectionResult, ElectionRound, Header, HeaderT};
+use crate::{api, backfill, ingestion, prometheus, tls, LOG_TARGET};
+use polkadot_sdk::sp_npos_elections::ElectionScore;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::{mpsc, watch},
+    task::JoinSet,
+};
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct MonitorOpts {
+    /// This listen addr to listen on for a REST API to query the database.
+    #[clap(long, default_value_t = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 9999)), env = "LISTEN_ADDR")]
+    listen_addr: SocketAddr,
+    /// Serve `/metrics` on this address instead of on `--listen-addr` together with the REST API.
+    ///
+    /// The metrics are served even with `--no-api`.
+    #[clap(long, env = "METRICS_ADDR")]
+    metrics_addr: Option<SocketAddr>,
+    /// Require this key as `Authorization: Bearer <key>` or `X-API-Key: <key>` header
+    /// on all routes of the REST API except `/health` and, unless `--docs-require-auth` is set, `/docs/`.
+    ///
+    /// Without it, the REST API is open to anyone who can reach it.
+    #[clap(long, env = "API_KEY", hide_env_values = true)]
+    api_key: Option<ApiKey>,
+    /// Require the `--api-key` on the `/docs/` routes too, which reveal the full API surface.
+    #[clap(long, env = "DOCS_REQUIRE_AUTH", requires = "api_key")]
+    docs_require_auth: bool,
+    /// Don't serve the REST API and only write the data to the database.
+    #[clap(long, env = "NO_API")]
+    no_api: bool,
+    /// The prefix of all prometheus metric names, e.g. `kusama` for `kusama_election_status`.
+    #[clap(long, default_value = "polkadot", env = "METRICS_PREFIX")]
+    metrics_prefix: String,
+    /// Label the `submissions_total` and `failed_submissions_total` metrics with the address of each submitter.
+    ///
+    /// This adds one time series per miner, which may be many on chains with many miners.
+    #[clap(long, env = "PER_MINER_METRICS")]
+    per_miner_metrics: bool,
+    /// What to do if the metadata couldn't be updated after a runtime upgrade.
+    ///
+    /// `keep` continues with the old metadata, `halt` shuts down the monitor and
+    /// `alert` logs an error and sets the `<prefix>_metadata_stale` gauge but continues.
+    #[clap(long, value_enum, default_value_t = MetadataUpdateFailure::Keep, env = "ON_METADATA_UPDATE_FAILURE")]
+    on_metadata_update_failure: MetadataUpdateFailure,
+    /// Where to read the finalized blocks from.
+    ///
+    /// `subscribe` subscribes to the finalized blocks and `poll` periodically
+    /// queries the finalized head, for RPC nodes that don't allow subscriptions.
+    #[clap(long, value_enum, default_value_t = BlockSource::Subscribe, env = "BLOCK_SOURCE")]
+    block_source: BlockSource,
+    /// Which blocks to follow.
+    ///
+    /// `finalized` only processes finalized blocks, `best` processes the best blocks
+    /// as soon as they are imported, which saves the finality delay but may record
+    /// data of blocks that are reverted by a reorg.
+    #[clap(long, value_enum, default_value_t = Follow::Finalized, env = "FOLLOW")]
+    follow: Follow,
+    /// The maximum time in seconds to wait on SIGINT or SIGTERM for the servers to stop
+    /// and the running database queries to complete before exiting anyway.
+    ///
+    /// This includes processing the blocks that wait for `--confirmation-depth` and the elections
+    /// that wait for `--unsigned-grace-blocks` further blocks to be finalized, which are lost
+    /// if they aren't written in time.
+    #[clap(long, default_value_t = 10, env = "SHUTDOWN_TIMEOUT")]
+    shutdown_timeout: u64,
+    /// The interval in seconds to poll the finalized head when `--block-source poll` is used.
+    #[clap(long, default_value_t = 6, env = "POLL_INTERVAL")]
+    poll_interval: u64,
+    /// Only keep the data of the most recent `N` rounds and periodically delete older rows.
+    ///
+    /// By default, all data is kept.
+    #[clap(long, env = "RETENTION_ROUNDS")]
+    retention_rounds: Option<u32>,
+    /// The interval in seconds to delete data older than `--retention-rounds`.
+    #[clap(long, default_value_t = 3600, env = "RETENTION_INTERVAL")]
+    retention_interval: u64,
+    /// How to count the rows of the `<prefix>_db_rows` gauge.
+    ///
+    /// `estimate` uses the estimate of the query planner which is much cheaper than `exact` for large tables.
+    #[clap(long, value_enum, default_value_t = RowCount::Estimate, env = "DB_ROWS_COUNT")]
+    db_rows_count: RowCount,
+    /// The interval in seconds to refresh the `<prefix>_db_rows` gauge.
+    #[clap(long, default_value_t = 60, env = "DB_ROWS_INTERVAL")]
+    db_rows_interval: u64,
+    /// Only process a finalized block once `N` further finalized blocks have been observed on top of it.
+    #[clap(long, default_value_t = 0, env = "CONFIRMATION_DEPTH")]
+    confirmation_depth: usize,
+    /// Don't apply runtime upgrades to the metadata and keep decoding with the metadata
+    /// the monitor was started with.
+    #[clap(long, env = "NO_AUTO_METADATA_UPDATE")]
+    no_auto_metadata_update: bool,
+    /// `/ready` reports the monitor as not ready if the most recent processed block
+    /// is more than this many seconds old.
+    #[clap(long, env = "MAX_HEALTHY_LAG_SECONDS")]
+    max_healthy_lag_seconds: Option<u64>,
+    /// After an election was finalized without a `Rewarded` event, wait for this many further
+    /// blocks to be finalized and read their `Rewarded` events before concluding the election was unsigned.
+    #[clap(long, default_value_t = 0, env = "UNSIGNED_GRACE_BLOCKS")]
+    unsigned_grace_blocks: u32,
+    /// Re-establish the block stream if no finalized block was received for this many seconds.
+    ///
+    /// This should be well above the block time and `--poll-interval`.
+    #[clap(long, default_value_t = 60, env = "BLOCK_TIMEOUT")]
+    block_timeout: u64,
+    /// The interval in seconds to check that no recent finalized block was skipped
+    /// and to process the skipped blocks.
+    #[clap(long, default_value_t = 60, env = "GAP_CHECK_INTERVAL")]
+    gap_check_interval: u64,
+    /// Serve the REST API over HTTPS with this PEM encoded certificate chain, requires `--tls-key`.
+    ///
+    /// The certificate and the key are reloaded on `SIGHUP`.
+    #[clap(long, requires = "tls_key", env = "TLS_CERT")]
+    tls_cert: Option<PathBuf>,
+    /// The PEM encoded private key of `--tls-cert`.
+    #[clap(long, requires = "tls_cert", env = "TLS_KEY")]
+    tls_key: Option<PathBuf>,
+    /// Record the extrinsics of `--config-change-calls` in the `config_changes` table.
+    ///
+    /// Only top-level extrinsics are matched, calls dispatched by governance e.g. through
+    /// `Sudo::sudo` or an enacted referendum aren't recorded.
+    #[clap(long, env = "RECORD_CONFIG_CHANGES")]
+    record_config_changes: bool,
+    /// The calls to record with `--record-config-changes` as a comma-separated list of `Pallet::call`.
+    #[clap(
+        long,
+        value_delimiter = ',',
+        default_value = "Staking::set_validator_count,Staking::increase_validator_count,Staking::scale_validator_count,Staking::set_staking_configs,ElectionProviderMultiPhase::set_minimum_untrusted_score,ElectionProviderMultiPhase::set_emergency_election_result",
+        env = "CONFIG_CHANGE_CALLS"
+    )]
+    config_change_calls: Vec<String>,
+    /// Before following the finalized blocks, read the blocks from this block up to the most
+    /// recent finalized block.
+    ///
+    /// The blocks of rounds that already have an election in the database are skipped,
+    /// so restarting with the same value doesn't insert their rows again.
+    #[clap(long, env = "FROM_BLOCK")]
+    from_block: Option<u64>,
+}
+
+/// Follow the finalized blocks, write the election data to the database and serve the REST API.
+pub async fn run(client: Client, db: Database, opts: MonitorOpts) -> anyhow::Result<()> {
+    let MonitorOpts {
+        listen_addr,
+        metrics_addr,
+        api_key,
+        docs_require_auth,
+        no_api,
+        metrics_prefix,
+        per_miner_metrics,
+        on_metadata_update_failure,
+        block_source,
+        follow,
+        shutdown_timeout,
+        poll_interval,
+        retention_rounds,
+        retention_interval,
+        db_rows_count,
+        db_rows_interval,
+        confirmation_depth,
+        no_auto_metadata_update,
+        max_healthy_lag_seconds,
+        unsigned_grace_blocks,
+        block_timeout,
+        gap_check_interval,
+        tls_cert,
+        tls_key,
+        record_config_changes,
+        config_change_calls,
+        from_block,
+    } = opts;
+
+    let prometheus = prometheus::setup_metrics_recorder(&metrics_prefix, per_miner_metrics)?;
+
+    let (stop_tx, mut stop_rx) = mpsc::channel(1);
+
+    tokio::spawn(db_rows_task(
+        db.clone(),
+        db_rows_count,
+        Duration::from_secs(db_rows_interval),
+    ));
+
+    let (gaps_tx, mut gaps_rx) = mpsc::channel(1);
+    tokio::spawn(block_gaps_task(
+        gaps_tx,
+        Duration::from_secs(gap_check_interval),
+    ));
+
+    if let Some(rounds) = retention_rounds {
+        tokio::spawn(retention_task(
+            db.clone(),
+            rounds,
+            Duration::from_secs(retention_interval),
+        ));
+    }
+
+    let state = AppState {
+        db: Arc::new(db.clone()),
+        prometheus: prometheus.clone(),
+        client: client.clone(),
+        max_healthy_lag: max_healthy_lag_seconds.map(Duration::from_secs),
+    };
+    // The servers stop once `stop_rx` is dropped.
+    let mut servers = Vec::new();
+
+    if let Some(metrics_addr) = metrics_addr {
+        let stop_tx2 = stop_tx.clone();
+        let listener = tokio::net::TcpListener::bind(&metrics_addr).await?;
+        let app = axum::Router::new()
+            .route("/metrics", axum::routing::get(routes::metrics))
+            .with_state(state.clone());
+        tracing::info!(target: LOG_TARGET, "Serving the metrics on {metrics_addr}");
+
+        servers.push(tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app)
+                .with_graceful_shutdown(async move { stop_tx2.closed().await })
+                .await
+            {
+                tracing::error!(target: LOG_TARGET, "Metrics server error: {:?}", e);
+            }
+        }));
+    }
+
+    if no_api {
+        tracing::info!(target: LOG_TARGET, "REST API disabled");
+    } else {
+        let stop_tx2 = stop_tx.clone();
+        let listener = tokio::net::TcpListener::bind(&listen_addr).await?;
+        let (mut router, _) = api(metrics_addr.is_none());
+        if let Some(key) = api_key {
+            let auth = ApiAuth {
+                key,
+                docs_require_auth,
+            };
+            router = router.layer(axum::middleware::from_fn_with_state(auth, routes::api_key));
+        }
+        let app = router.with_state(state);
+        let shutdown = async move {
+            stop_tx2.closed().await;
+        };
+
+        match tls_cert.zip(tls_key) {
+            Some((cert, key)) => {
+                let config = tls::load_config(&cert, &key)?;
+                tracing::info!(target: LOG_TARGET, "Serving the REST API over HTTPS");
+
+                servers.push(tokio::spawn(async move {
+                    if let Err(e) = tls::serve(listener, app, (cert, key), config, shutdown).await {
+                        tracing::error!(target: LOG_TARGET, "Server error: {:?}", e);
+                    }
+                }));
+            }
+            None => {
+                servers.push(tokio::spawn(async move {
+                    if let Err(e) = axum::serve(listener, app)
+                        .with_graceful_shutdown(shutdown)
+                        .await
+                    {
+                        tracing::error!(target: LOG_TARGET, "Server error: {:?}", e);
+                    }
+                }));
+            }
+        }
+    }
+
+    let mut state = resume_round_state(&client, &db).await?;
+
+    // The last block read by the backfill, the live blocks up to it are skipped.
+    let mut backfilled_to = None;
+    if let Some(from_block) = from_block {
+        let to = get_finalized_number(client.rpc()).await?;
+
+        if from_block <= to {
+            backfill::read_blocks(
+                &client,
+                &db,
+                &mut state,
+                from_block,
+                to,
+                unsigned_grace_blocks,
+            )
+            .await?;
+            db.save_round_state(&state).await?;
+            backfilled_to = Some(to);
+        }
+    }
+
+    let poll_interval = Duration::from_secs(poll_interval);
+    let block_timeout = Duration::from_secs(block_timeout);
+    let mut blocks = finalized_blocks(&client, block_source, follow, poll_interval).await?;
+
+    // The elections that are inserted once the blocks after them were checked for late rewards.
+    let mut late_rewards = JoinSet::new();
+    // The finalized blocks that don't have `confirmation_depth` blocks on top of them yet.
+    let mut unconfirmed = VecDeque::with_capacity(confirmation_depth + 1);
+
+    // The spec version of the latest runtime upgrade, to mark where it happened in the logs.
+    let (upgrades_tx, mut upgrades_rx) = watch::channel(None);
+
+    // The main loop stops if all senders are dropped, so keep it alive if there is no upgrade task.
+    let _stop_tx = if no_auto_metadata_update {
+        tracing::info!(target: LOG_TARGET, "Automatic metadata updates disabled");
+        Some(stop_tx)
+    } else {
+        tokio::spawn(runtime_upgrade_task(
+            client.chain_api().clone(),
+            stop_tx,
+            upgrades_tx,
+            on_metadata_update_failure,
+        ));
+        None
+    };
+
+    let mut stream_int = signal(SignalKind::interrupt())?;
+    let mut stream_term = signal(SignalKind::terminate())?;
+
+    loop {
+        let (block, _) = tokio::select! {
+            _ = stream_int.recv() => break,
+            _ = stream_term.recv() => break,
+            msg = stop_rx.recv() => {
+                let msg = msg.unwrap_or_else(|| "Unknown".to_string());
+                return Err(anyhow::anyhow!("Upgrade task failed: {msg}"));
+            }
+            Some(res) = late_rewards.join_next() => {
+                res??;
+                continue;
+            }
+            Some(missing) = gaps_rx.recv() => {
+                process_missed_blocks(&client, &db, &mut state, &missing, unsigned_grace_blocks, &mut late_rewards).await?;
+                db.save_round_state(&state).await?;
+                continue;
+            }
+            block = tokio::time::timeout(block_timeout, blocks.next()) => {
+                let Ok(block) = block else {
+                    tracing::warn!(target: LOG_TARGET, "No block received for {}s, re-establishing the block stream", block_timeout.as_secs());
+                    prometheus::record_block_stream_watchdog_trigger();
+                    blocks = finalized_blocks(&client, block_source, follow, poll_interval).await?;
+                    continue;
+                };
+
+                match block {
+                    Some(Ok(block)) => {
+                        block
+                    }
+                    Some(Err(e)) => {
+                        if e.is_disconnected_will_reconnect() {
+                            continue;
+                        }
+                        return Err(e.into());
+                    }
+                    None => {
+                        blocks = finalized_blocks(&client, block_source, follow, poll_interval).await?;
+                        continue;
+                    }
+                }
+            }
+        };
+
+        ingestion::record_finalized_head(block.number());
+        let finalized_head = block.number();
+
+        if backfilled_to.is_some_and(|to| u64::from(block.number()) <= to) {
+            continue;
+        }
+
+        unconfirmed.push_back(block);
+        if unconfirmed.len() <= confirmation_depth {
+            continue;
+        }
+        let block = unconfirmed
+            .pop_front()
+            .expect("More than confirmation_depth blocks; qed");
+
+        if record_config_changes {
+            read_config_changes(&client, &db, &block, &config_change_calls).await?;
+        }
+
+        // Fails if the upgrade task stopped, which then reported its error on `stop_tx`.
+        if upgrades_rx.has_changed().unwrap_or(false) {
+            if let Some(spec_version) = *upgrades_rx.borrow_and_update() {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    block = block.number(),
+                    round = state.round(),
+                    spec_version,
+                    "Runtime upgraded, the following blocks are decoded with the new metadata"
+                );
+            }
+        }
+
+        let now = Instant::now();
+        process_block(
+            &client,
+            &db,
+            &mut state,
+            &block,
+            unsigned_grace_blocks,
+            &mut late_rewards,
+        )
+        .await?;
+        prometheus::record_block_processing(now.elapsed());
+        prometheus::record_block_lag(finalized_head.saturating_sub(block.number()));
+        db.save_round_state(&state).await?;
+    }
+
+    let flush = async {
+        // The blocks are finalized, they only wait for further confirmations.
+        while let Some(block) = unconfirmed.front() {
+            if record_config_changes {
+                read_config_changes(&client, &db, block, &config_change_calls).await?;
+            }
+            process_block(
+                &client,
+                &db,
+                &mut state,
+                block,
+                unsigned_grace_blocks,
+                &mut late_rewards,
+            )
+            .await?;
+            db.save_round_state(&state).await?;
+            unconfirmed.pop_front();
+        }
+
+        while let Some(res) = late_rewards.join_next().await {
+            if let Err(e) = res.map_err(anyhow::Error::from).and_then(|res| res) {
+                tracing::error!(target: LOG_TARGET, "Failed to insert an election after the late rewards: {e}");
+            }
+        }
+        Ok(())
+    };
+
+    if !shutdown(
+        &db,
+        stop_rx,
+        servers,
+        flush,
+        Duration::from_secs(shutdown_timeout),
+    )
+    .await
+    {
+        tracing::error!(
+            target: LOG_TARGET,
+            "{} blocks waiting for confirmations and {} elections waiting for late rewards weren't written",
+            unconfirmed.len(),
+            late_rewards.len()
+        );
+    }
+    Ok(())
+}
+
+/// Stop the servers, write what is still queued with `flush` and close the database
+/// connections, or give up after `timeout`.
+///
+/// Signals are only handled between blocks, so the block that was processed when the signal
+/// arrived is complete. Returns whether `flush` completed.
+async fn shutdown(
+    db: &Database,
+    stop_rx: mpsc::Receiver<String>,
+    servers: Vec<tokio::task::JoinHandle<()>>,
+    flush: impl Future<Output = anyhow::Result<()>>,
+    timeout: Duration,
+) -> bool {
+    tracing::info!(target: LOG_TARGET, "Shutting down");
+    drop(stop_rx);
+    let deadline = Instant::now() + timeout;
+
+    let graceful = async {
+        for server in servers {
+            _ = server.await;
+        }
+        flush.await
+    };
+
+    let flushed = match tokio::time::timeout(timeout, graceful).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            tracing::error!(target: LOG_TARGET, "Failed to write the queued data: {e}");
+            false
+        }
+        Err(_) => false,
+    };
+    let closed = db
+        .close(deadline.saturating_duration_since(Instant::now()))
+        .await;
+
+    if !closed || Instant::now() >= deadline {
+        tracing::warn!(
+            target: LOG_TARGET,
+            "Shutdown didn't complete within {}s, exiting anyway",
+            timeout.as_secs()
+        );
+    }
+    flushed
+}
+
+/// Load the round state stored by the previous run if its round is still active on chain.
+async fn resume_round_state(client: &Client, db: &Database) -> anyhow::Result<ElectionRound> {
+    let Some(state) = db.get_round_state().await? else {
+        return Ok(ElectionRound::new());
+    };
+    if state.round().is_none() {
+        return Ok(ElectionRound::new());
+    }
+
+    let finalized = get_block(client, get_finalized_number(client.rpc()).await?).await?;
+    let round = get_round(client, finalized.hash()).await?;
+
+    Ok(resume(state, round))
+}
+
+/// The stored `state` if it's of the active `round`, a new state otherwise.
+fn resume(state: ElectionRound, round: u32) -> ElectionRound {
+    match state.round() {
+        Some(stored_round) if stored_round == round => {
+            tracing::info!(
+                target: LOG_TARGET,
+                round,
+                block = state.first_block().unwrap_or_default(),
+                "Resuming the stored round"
+            );
+            state
+        }
+        stored_round => {
+            tracing::info!(
+                target: LOG_TARGET,
+                ?stored_round,
+                round,
+                "Discarding the stored state of a previous round"
+            );
+            ElectionRound::new()
+        }
+    }
+}
+
+/// Process blocks that were skipped.
+///
+/// While a round is active, they are processed with its state, so that an election
+/// finalized in a skipped block completes the round and only the blocks before the
+/// processed ones are read again. Otherwise they are processed with a separate state
+/// so that the next round isn't affected.
+async fn process_missed_blocks(
+    client: &Client,
+    db: &Database,
+    state: &mut ElectionRound,
+    blocks: &[u32],
+    unsigned_grace_blocks: u32,
+    late_rewards: &mut JoinSet<anyhow::Result<()>>,
+) -> anyhow::Result<()> {
+    let mut separate = ElectionRound::new();
+
+    for n in blocks {
+        let block = get_block(client, *n as u64).await?;
+        let state = if state.waiting_for_election_finalized() {
+            &mut *state
+        } else {
+            &mut separate
+        };
+        process_block(
+            client,
+            db,
+            state,
+            &block,
+            unsigned_grace_blocks,
+            late_rewards,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Read a block and insert its election data into the database.
+///
+/// If the election was finalized in the block, the remaining blocks of the round
+/// are read as well and the election is inserted.
+///
+/// An election without a `Rewarded` event is only concluded to be unsigned once
+/// the `unsigned_grace_blocks` blocks after it were checked for a late reward.
+/// The election is then inserted by a task in `late_rewards`, so that the following
+/// blocks aren't held up until these blocks are finalized.
+pub async fn process_block(
+    client: &Client,
+    db: &Database,
+    state: &mut ElectionRound,
+    block: &Header,
+    unsigned_grace_blocks: u32,
+    late_rewards: &mut JoinSet<anyhow::Result<()>>,
+) -> anyhow::Result<()> {
+    let curr_phase = get_phase(client, block.hash()).await?.0;
+    let round = get_round(client, block.hash()).await?;
+    let timestamp = get_timestamp(client, block.hash()).await?;
+    ingestion::record_block_timestamp(timestamp);
+
+    tracing::info!(
+        target: LOG_TARGET,
+        block = block.number(),
+        phase = ?curr_phase,
+        round,
+        "Processing block"
+    );
+
+    prometheus::record_in_election_phase(curr_phase.is_signed() || curr_phase.is_unsigned_open());
+
+    if !curr_phase.is_signed()
+        && !curr_phase.is_unsigned_open()
+        && !state.waiting_for_election_finalized()
+    {
+        state.clear();
+        ingestion::record_processed_block(block.number());
+        return Ok(());
+    }
+
+    if let Some(prev) = state.new_block(block.number() as u64, block.hash(), round) {
+        delete_reverted_block(db, block, prev).await?;
+    }
+
+    let election_finalized = match read_block(client, block, state, db).await? {
+        ReadBlock::PhaseClosed => unreachable!("Phase already checked; qed"),
+        ReadBlock::ElectionFinalized(winner) => {
+            read_remaining_blocks_in_round(client, state, block.number() as u64, db).await?;
+            winner
+        }
+        ReadBlock::Done => {
+            ingestion::record_processed_block(block.number());
+            return Ok(());
+        }
+    };
+
+    tracing::debug!(target: LOG_TARGET, "state {:?}", state);
+    let signed_submissions = state.signed_submissions();
+    let (election_result, round) = state.complete();
+    let score = election_finalized.score.0;
+
+    if election_result == ElectionResult::Unsigned && unsigned_grace_blocks > 0 {
+        let (client, db, block) = (client.clone(), db.clone(), block.clone());
+        late_rewards.spawn(async move {
+            let winners =
+                read_late_rewards(&client, block.number() as u64, unsigned_grace_blocks).await?;
+            let election_result = if winners.is_empty() {
+                ElectionResult::Unsigned
+            } else {
+                ElectionResult::Signed(winners)
+            };
+            insert_election(
+                &client,
+                &db,
+                &block,
+                election_result,
+                round,
+                signed_submissions,
+                score,
+            )
+            .await
+        });
+    } else {
+        insert_election(
+            client,
+            db,
+            block,
+            election_result,
+            round,
+            signed_submissions,
+            score,
+        )
+        .await?;
+    }
+    ingestion::record_processed_block(block.number());
+
+    Ok(())
+}
+
+/// Insert the election finalized in `block` with the data read from the chain at that block.
+async fn insert_election(
+    client: &Client,
+    db: &dyn Store,
+    block: &Header,
+    election_result: ElectionResult,
+    round: u32,
+    signed_submissions: u32,
+    score: ElectionScore,
+) -> anyhow::Result<()> {
+    if election_result == ElectionResult::Unsigned && signed_submissions == 0 {
+        tracing::warn!(
+            target: LOG_TARGET,
+            block = block.number(),
+            round,
+            "The round was finalized by an unsigned solution without any signed submission"
+        );
+        prometheus::record_round_without_signed_submission();
+    }
+
+    let block_author = get_block_author(client, block)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!(target: LOG_TARGET, block = block.number(), "Failed to get the block author: {e}");
+            None
+        });
+
+    let met_target = match get_election_targets(client, block.parent_hash).await {
+        Ok(Some((winners, desired_targets))) => {
+            if winners < desired_targets {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    block = block.number(),
+                    winners,
+                    desired_targets,
+                    "The election elected fewer winners than desired"
+                );
+            }
+            prometheus::record_met_target(winners >= desired_targets);
+            Some(winners >= desired_targets)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!(target: LOG_TARGET, block = block.number(), "Failed to get the election targets: {e}");
+            None
+        }
+    };
+
+    let winners = get_elected(client, block.parent_hash)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!(target: LOG_TARGET, block = block.number(), "Failed to get the elected validators: {e}");
+            None
+        });
+
+    // The snapshot is removed when the election is finalized.
+    let snapshot_size = match get_snapshot_size(client, block.parent_hash).await {
+        Ok(Some((voters, targets))) => {
+            prometheus::record_snapshot_size(voters, targets);
+            Some((voters, targets))
+        }
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!(target: LOG_TARGET, block = block.number(), "Failed to get the snapshot size: {e}");
+            None
+        }
+    };
+
+    let phase_durations = db
+        .get_phase_durations(round, block.number())
+        .await
+        .map_err(|e| {
+            tracing::warn!(target: LOG_TARGET, round, "Failed to get the phase durations: {e}");
+        })
+        .ok()
+        .filter(|d| !d.is_empty());
+
+    prometheus::record_election(&election_result);
+    prometheus::record_signed_streak(&election_result);
+    prometheus::record_election_score(&score);
+    db.insert_election(
+        Election::new(election_result, round, block.number(), score)
+            .with_block_author(block_author)
+            .with_met_target(met_target)
+            .with_phase_durations(phase_durations)
+            .with_snapshot_size(snapshot_size)
+            .with_winners(winners)
+            .with_spec_version(get_spec_version(client, block.hash()).await)
+            .with_created_at(to_date_time(get_timestamp(client, block.hash()).await?)?),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Hash;
+
+    fn stored_round(round: u32) -> ElectionRound {
+        let mut state = ElectionRound::new();
+        state.new_block(100, Hash::from_low_u64_be(100), round);
+        state.add_signed_submission();
+        state
+    }
+
+    #[test]
+    fn resume_the_active_round() {
+        let state = resume(stored_round(7), 7);
+
+        assert_eq!(state.round(), Some(7));
+        assert_eq!(state.first_block(), Some(100));
+        assert_eq!(state.signed_submissions(), 1);
+    }
+
+    #[test]
+    fn discard_a_stale_round() {
+        let state = resume(stored_round(6), 7);
+
+        assert_eq!(state, ElectionRound::new());
+        assert!(!state.waiting_for_election_finalized());
+    }
+}
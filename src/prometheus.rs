@@ -1,28 +1,177 @@
+pub use block_gaps::record_block_gaps;
+pub use block_processing::{record_block_lag, record_block_processing};
+pub use block_stream_watchdog::record_block_stream_watchdog_trigger;
+pub use db_rows::record_db_rows;
+pub use election_score::record_election_score;
 pub use election_status::record_election;
+pub use http_requests::record_http_request;
+pub use in_election_phase::record_in_election_phase;
+pub use met_target::record_met_target;
+pub use metadata_stale::record_metadata_stale;
 pub use metrics_exporter_prometheus::PrometheusHandle;
+pub use no_signed_submission::record_round_without_signed_submission;
+pub use reorgs::record_reorg;
+pub use runtime_upgrades::record_runtime_upgrade;
+pub use signed_streak::record_signed_streak;
+pub use snapshot_size::record_snapshot_size;
+pub use submissions::{record_failed_submission, record_submission};
+pub use unmatched_solution_stored::record_unmatched_solution_stored;
+pub use unprocessed_block_backlog::record_unprocessed_block_backlog;
 
-use metrics::describe_gauge;
-use metrics_exporter_prometheus::PrometheusBuilder;
+use metrics::{describe_counter, describe_gauge, describe_histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+use std::sync::{atomic::Ordering, OnceLock};
 
-pub fn setup_metrics_recorder() -> anyhow::Result<PrometheusHandle> {
-    let handle = PrometheusBuilder::new().install_recorder()?;
-    describe_gauge!(election_status::TARGET, election_status::DESCRIPTION);
-    metrics::gauge!(election_status::TARGET)
+/// The prefix of all metric names e.g, `polkadot`.
+static PREFIX: OnceLock<String> = OnceLock::new();
+
+/// Label the submission counters with the submitter's address if `per_miner` is set.
+pub fn setup_metrics_recorder(prefix: &str, per_miner: bool) -> anyhow::Result<PrometheusHandle> {
+    if !is_valid_prefix(prefix) {
+        return Err(anyhow::anyhow!(
+            "Invalid metrics prefix `{prefix}`, must match [a-zA-Z_][a-zA-Z0-9_]*"
+        ));
+    }
+    PREFIX
+        .set(prefix.to_string())
+        .map_err(|_| anyhow::anyhow!("Metrics recorder already initialized"))?;
+
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(metric_name(http_requests::DURATION_NAME)),
+            http_requests::DURATION_BUCKETS,
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full(metric_name(block_processing::DURATION_NAME)),
+            block_processing::DURATION_BUCKETS,
+        )?
+        .install_recorder()?;
+    describe_gauge!(
+        metric_name(election_status::NAME),
+        election_status::DESCRIPTION
+    );
+    metrics::gauge!(metric_name(election_status::NAME))
         .set(election_status::ElectionStatus::Uninitialized as u32);
+    describe_gauge!(
+        metric_name(metadata_stale::NAME),
+        metadata_stale::DESCRIPTION
+    );
+    metrics::gauge!(metric_name(metadata_stale::NAME)).set(0);
+    describe_gauge!(
+        metric_name(in_election_phase::NAME),
+        in_election_phase::DESCRIPTION
+    );
+    metrics::gauge!(metric_name(in_election_phase::NAME)).set(0);
+    describe_gauge!(metric_name(signed_streak::NAME), signed_streak::DESCRIPTION);
+    metrics::gauge!(metric_name(signed_streak::NAME)).set(0);
+    describe_gauge!(metric_name(met_target::NAME), met_target::DESCRIPTION);
+    describe_gauge!(metric_name(db_rows::NAME), db_rows::DESCRIPTION);
+    describe_gauge!(
+        metric_name(snapshot_size::VOTERS_NAME),
+        snapshot_size::VOTERS_DESCRIPTION
+    );
+    describe_gauge!(
+        metric_name(snapshot_size::TARGETS_NAME),
+        snapshot_size::TARGETS_DESCRIPTION
+    );
+    describe_gauge!(
+        metric_name(election_score::MINIMAL_STAKE_NAME),
+        election_score::MINIMAL_STAKE_DESCRIPTION
+    );
+    describe_gauge!(
+        metric_name(election_score::SUM_STAKE_NAME),
+        election_score::SUM_STAKE_DESCRIPTION
+    );
+    describe_counter!(
+        metric_name(unmatched_solution_stored::NAME),
+        unmatched_solution_stored::DESCRIPTION
+    );
+    metrics::counter!(metric_name(unmatched_solution_stored::NAME)).absolute(0);
+    describe_gauge!(
+        metric_name(unprocessed_block_backlog::NAME),
+        unprocessed_block_backlog::DESCRIPTION
+    );
+    metrics::gauge!(metric_name(unprocessed_block_backlog::NAME)).set(0);
+    describe_histogram!(
+        metric_name(block_processing::DURATION_NAME),
+        block_processing::DURATION_DESCRIPTION
+    );
+    describe_gauge!(
+        metric_name(block_processing::LAG_NAME),
+        block_processing::LAG_DESCRIPTION
+    );
+    metrics::gauge!(metric_name(block_processing::LAG_NAME)).set(0);
+    describe_counter!(metric_name(block_gaps::NAME), block_gaps::DESCRIPTION);
+    metrics::counter!(metric_name(block_gaps::NAME)).absolute(0);
+    describe_counter!(
+        metric_name(block_stream_watchdog::NAME),
+        block_stream_watchdog::DESCRIPTION
+    );
+    metrics::counter!(metric_name(block_stream_watchdog::NAME)).absolute(0);
+    describe_counter!(
+        metric_name(no_signed_submission::NAME),
+        no_signed_submission::DESCRIPTION
+    );
+    metrics::counter!(metric_name(no_signed_submission::NAME)).absolute(0);
+    describe_counter!(metric_name(reorgs::NAME), reorgs::DESCRIPTION);
+    metrics::counter!(metric_name(reorgs::NAME)).absolute(0);
+    describe_counter!(
+        metric_name(runtime_upgrades::NAME),
+        runtime_upgrades::DESCRIPTION
+    );
+    submissions::PER_MINER.store(per_miner, Ordering::Relaxed);
+    describe_counter!(metric_name(submissions::NAME), submissions::DESCRIPTION);
+    describe_counter!(
+        metric_name(submissions::FAILED_NAME),
+        submissions::FAILED_DESCRIPTION
+    );
+    // Without the address label the series are known upfront, start them at 0 so that
+    // an alert on their increase fires on the first failure.
+    if !per_miner {
+        for reason in submissions::FAILURE_REASONS {
+            metrics::counter!(metric_name(submissions::FAILED_NAME), "reason" => reason.as_str())
+                .absolute(0);
+        }
+    }
+    describe_counter!(
+        metric_name(http_requests::TOTAL_NAME),
+        http_requests::TOTAL_DESCRIPTION
+    );
+    describe_histogram!(
+        metric_name(http_requests::DURATION_NAME),
+        metrics::Unit::Seconds,
+        http_requests::DURATION_DESCRIPTION
+    );
     Ok(handle)
 }
 
+/// Returns the full name of a metric i.e, `<prefix>_<name>`.
+fn metric_name(name: &str) -> String {
+    let prefix = PREFIX.get().map(String::as_str).unwrap_or("polkadot");
+    format!("{prefix}_{name}")
+}
+
+fn is_valid_prefix(prefix: &str) -> bool {
+    let mut chars = prefix.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 pub(super) mod election_status {
+    use super::metric_name;
     use crate::types::ElectionResult;
 
-    pub(super) const TARGET: &str = "polkadot_election_status";
-    pub(super) const DESCRIPTION: &str = "The outcome of the most recent election represented as an integer. 0 if no election has occurred yet this is a placeholder value, 1 if the election succeeded based on an unsigned solution, 2 if the election succeeded based on a signed solution or 3 if the election failed.";
+    pub(super) const NAME: &str = "election_status";
+    pub(super) const DESCRIPTION: &str = "The outcome of the most recent election represented as an integer. 0 if no election has occurred yet this is a placeholder value, 1 if the election succeeded based on an unsigned solution, 2 if the election succeeded based on a signed solution, 3 if the election failed or 4 if the election was finalized by a governance fallback solution.";
     #[repr(u32)]
     pub(super) enum ElectionStatus {
         Uninitialized = 0,
         Unsigned = 1,
         Signed = 2,
         Failed = 3,
+        GovernanceFallback = 4,
     }
 
     pub fn record_election(election_result: &ElectionResult) {
@@ -30,7 +179,277 @@ pub(super) mod election_status {
             ElectionResult::Failed => ElectionStatus::Failed,
             ElectionResult::Unsigned => ElectionStatus::Unsigned,
             ElectionResult::Signed(_) => ElectionStatus::Signed,
+            ElectionResult::GovernanceFallback => ElectionStatus::GovernanceFallback,
         };
-        metrics::gauge!(TARGET).set(val as u32);
+        metrics::gauge!(metric_name(NAME)).set(val as u32);
+    }
+}
+
+pub(super) mod metadata_stale {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "metadata_stale";
+    pub(super) const DESCRIPTION: &str = "1 if the most recent runtime upgrade couldn't be applied and the metadata used to decode the chain data may be outdated, 0 otherwise.";
+
+    pub fn record_metadata_stale(stale: bool) {
+        metrics::gauge!(metric_name(NAME)).set(stale as u32);
+    }
+}
+
+pub(super) mod in_election_phase {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "in_election_phase";
+    pub(super) const DESCRIPTION: &str =
+        "1 if the most recent processed block is in the signed or an open unsigned phase, 0 otherwise.";
+
+    pub fn record_in_election_phase(active: bool) {
+        metrics::gauge!(metric_name(NAME)).set(active as u32);
+    }
+}
+
+pub(super) mod signed_streak {
+    use super::metric_name;
+    use crate::types::ElectionResult;
+
+    pub(super) const NAME: &str = "consecutive_signed_elections";
+    pub(super) const DESCRIPTION: &str = "The number of consecutive elections that succeeded based on a signed solution. Reset to 0 on any other election outcome.";
+
+    pub fn record_signed_streak(election_result: &ElectionResult) {
+        let gauge = metrics::gauge!(metric_name(NAME));
+        match election_result {
+            ElectionResult::Signed(_) => gauge.increment(1),
+            _ => gauge.set(0),
+        }
+    }
+}
+
+pub(super) mod unmatched_solution_stored {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "unmatched_solution_stored_total";
+    pub(super) const DESCRIPTION: &str = "The number of signed or unsigned `SolutionStored` events that couldn't be matched to a submitted solution in the same block.";
+
+    pub fn record_unmatched_solution_stored() {
+        metrics::counter!(metric_name(NAME)).increment(1);
+    }
+}
+
+pub(super) mod met_target {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "election_met_target";
+    pub(super) const DESCRIPTION: &str = "1 if the number of validators elected in the most recent election matched the desired number of targets, 0 if fewer were elected.";
+
+    pub fn record_met_target(met_target: bool) {
+        metrics::gauge!(metric_name(NAME)).set(met_target as u32);
+    }
+}
+
+pub(super) mod db_rows {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "db_rows";
+    pub(super) const DESCRIPTION: &str = "The number of rows in each database table, either exact or estimated depending on `--db-rows-count`.";
+
+    pub fn record_db_rows(table: &'static str, rows: u64) {
+        metrics::gauge!(metric_name(NAME), "table" => table).set(rows as f64);
+    }
+}
+
+pub(super) mod unprocessed_block_backlog {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "unprocessed_block_backlog";
+    pub(super) const DESCRIPTION: &str = "The number of finalized blocks that are known but not processed yet with `--block-source poll`, always 0 otherwise.";
+
+    pub fn record_unprocessed_block_backlog(blocks: u64) {
+        metrics::gauge!(metric_name(NAME)).set(blocks as f64);
+    }
+}
+
+pub(super) mod block_processing {
+    use super::metric_name;
+    use std::time::Duration;
+
+    pub(super) const DURATION_NAME: &str = "block_processing_seconds";
+    pub(super) const DURATION_DESCRIPTION: &str =
+        "The time to process a finalized block in seconds, including the remaining blocks of the round if the election was finalized in it.";
+    pub(super) const DURATION_BUCKETS: &[f64] =
+        &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+    pub(super) const LAG_NAME: &str = "block_lag";
+    pub(super) const LAG_DESCRIPTION: &str =
+        "The number of blocks between the most recent finalized block and the most recent processed block, also while catching up with `--from-block`.";
+
+    pub fn record_block_processing(elapsed: Duration) {
+        metrics::histogram!(metric_name(DURATION_NAME)).record(elapsed.as_secs_f64());
+    }
+
+    pub fn record_block_lag(blocks: u32) {
+        metrics::gauge!(metric_name(LAG_NAME)).set(blocks);
+    }
+}
+
+pub(super) mod http_requests {
+    use super::metric_name;
+    use std::time::Duration;
+
+    pub(super) const TOTAL_NAME: &str = "http_requests_total";
+    pub(super) const TOTAL_DESCRIPTION: &str =
+        "The number of REST API requests per route and response status.";
+    pub(super) const DURATION_NAME: &str = "http_request_duration_seconds";
+    pub(super) const DURATION_DESCRIPTION: &str =
+        "The time to respond to REST API requests per route in seconds.";
+    pub(super) const DURATION_BUCKETS: &[f64] = &[
+        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+    ];
+
+    pub fn record_http_request(route: &str, status: u16, elapsed: Duration) {
+        metrics::counter!(
+            metric_name(TOTAL_NAME),
+            "route" => route.to_string(),
+            "status" => status.to_string()
+        )
+        .increment(1);
+        metrics::histogram!(metric_name(DURATION_NAME), "route" => route.to_string())
+            .record(elapsed.as_secs_f64());
+    }
+}
+
+pub(super) mod block_gaps {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "block_gaps_detected_total";
+    pub(super) const DESCRIPTION: &str =
+        "The number of finalized blocks that were skipped and processed later.";
+
+    pub fn record_block_gaps(blocks: u64) {
+        metrics::counter!(metric_name(NAME)).increment(blocks);
+    }
+}
+
+pub(super) mod snapshot_size {
+    use super::metric_name;
+
+    pub(super) const VOTERS_NAME: &str = "election_snapshot_voters";
+    pub(super) const VOTERS_DESCRIPTION: &str =
+        "The number of voters in the snapshot of the most recent election.";
+    pub(super) const TARGETS_NAME: &str = "election_snapshot_targets";
+    pub(super) const TARGETS_DESCRIPTION: &str =
+        "The number of targets in the snapshot of the most recent election.";
+
+    pub fn record_snapshot_size(voters: u32, targets: u32) {
+        metrics::gauge!(metric_name(VOTERS_NAME)).set(voters);
+        metrics::gauge!(metric_name(TARGETS_NAME)).set(targets);
+    }
+}
+
+pub(super) mod election_score {
+    use super::metric_name;
+    use polkadot_sdk::sp_npos_elections::ElectionScore;
+
+    pub(super) const MINIMAL_STAKE_NAME: &str = "election_minimal_stake";
+    pub(super) const MINIMAL_STAKE_DESCRIPTION: &str =
+        "The `minimal_stake` score of the winning solution of the most recent election, in plancks.";
+    pub(super) const SUM_STAKE_NAME: &str = "election_sum_stake";
+    pub(super) const SUM_STAKE_DESCRIPTION: &str =
+        "The `sum_stake` score of the winning solution of the most recent election, in plancks.";
+
+    pub fn record_election_score(score: &ElectionScore) {
+        metrics::gauge!(metric_name(MINIMAL_STAKE_NAME)).set(score.minimal_stake as f64);
+        metrics::gauge!(metric_name(SUM_STAKE_NAME)).set(score.sum_stake as f64);
+    }
+}
+
+pub(super) mod block_stream_watchdog {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "block_stream_watchdog_triggers_total";
+    pub(super) const DESCRIPTION: &str =
+        "The number of times the block stream was re-established because no block was received within `--block-timeout`.";
+
+    pub fn record_block_stream_watchdog_trigger() {
+        metrics::counter!(metric_name(NAME)).increment(1);
+    }
+}
+
+pub(super) mod submissions {
+    use super::metric_name;
+    use crate::types::{Address, FailureReason};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    pub(super) const NAME: &str = "submissions_total";
+    pub(super) const DESCRIPTION: &str =
+        "The number of submissions by whether they were successful, and by submitter with `--per-miner-metrics`.";
+    pub(super) const FAILED_NAME: &str = "failed_submissions_total";
+    pub(super) const FAILED_DESCRIPTION: &str =
+        "The number of submissions that weren't stored by the reason, and by submitter with `--per-miner-metrics`.";
+    pub(super) const FAILURE_REASONS: [FailureReason; 5] = [
+        FailureReason::StaleSnapshot,
+        FailureReason::LowScore,
+        FailureReason::QueueFull,
+        FailureReason::WrongPhase,
+        FailureReason::Invalid,
+    ];
+    /// Whether to add the `address` label, which has one value per miner.
+    pub(super) static PER_MINER: AtomicBool = AtomicBool::new(false);
+
+    pub fn record_submission(who: &Address, success: bool) {
+        let success = success.to_string();
+
+        if PER_MINER.load(Ordering::Relaxed) {
+            let address = who.to_ss58().unwrap_or_else(|| who.to_string());
+            metrics::counter!(metric_name(NAME), "address" => address, "success" => success)
+                .increment(1);
+        } else {
+            metrics::counter!(metric_name(NAME), "success" => success).increment(1);
+        }
+    }
+
+    pub fn record_failed_submission(who: &Address, reason: FailureReason) {
+        let reason = reason.as_str();
+
+        if PER_MINER.load(Ordering::Relaxed) {
+            let address = who.to_ss58().unwrap_or_else(|| who.to_string());
+            metrics::counter!(metric_name(FAILED_NAME), "address" => address, "reason" => reason)
+                .increment(1);
+        } else {
+            metrics::counter!(metric_name(FAILED_NAME), "reason" => reason).increment(1);
+        }
+    }
+}
+
+pub(super) mod runtime_upgrades {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "runtime_upgrades_total";
+    pub(super) const DESCRIPTION: &str =
+        "The number of runtime upgrades seen while running, labeled with the new spec `version`.";
+
+    pub fn record_runtime_upgrade(version: u32) {
+        metrics::counter!(metric_name(NAME), "version" => version.to_string()).increment(1);
+    }
+}
+
+pub(super) mod no_signed_submission {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "rounds_no_signed_submission_total";
+    pub(super) const DESCRIPTION: &str = "The number of rounds that were finalized by an unsigned solution without any signed submission, which may mean that all miners are down.";
+
+    pub fn record_round_without_signed_submission() {
+        metrics::counter!(metric_name(NAME)).increment(1);
+    }
+}
+
+pub(super) mod reorgs {
+    use super::metric_name;
+
+    pub(super) const NAME: &str = "reorgs_detected_total";
+    pub(super) const DESCRIPTION: &str =
+        "The number of blocks that were processed again with a different hash than before.";
+
+    pub fn record_reorg() {
+        metrics::counter!(metric_name(NAME)).increment(1);
     }
 }
@@ -3,33 +3,141 @@
 // see LICENSE for license details.
 
 use crate::{
-    db::{Database, Election, Slashed, Stats, Submission},
-    prometheus::PrometheusHandle,
+    db::{
+        self, ConfigChange, Ejection, Election, ElectionResultKind, ElectionWithSubmissions,
+        MinerDistribution, MinerStats, NeverSuccessfulMiner, Page, RoundActivity, Score,
+        SharedStore, Slashed, Snipes, Sort, Stats, Store, Submission, SubmissionsByAddress,
+        TimeRange, WinRate,
+    },
+    events::EventKind,
+    export,
+    helpers::SignedQueue,
+    ingestion::{self, Latency},
+    prometheus::{self, PrometheusHandle},
+    types::{Address, Client, Hash},
+    LOG_TARGET,
 };
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Body,
+    extract::{FromRef, MatchedPath, Path, Query, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
-use oasgen::oasgen;
-use std::num::NonZeroUsize;
+use futures::Stream;
+use oasgen::{oasgen, OaSchema};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::hash::{DefaultHasher, Hash as _, Hasher};
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
 
-type HttpError = (StatusCode, String);
+type HttpError = ApiError;
+
+/// The JSON body of all error responses.
+#[derive(Debug, Serialize, OaSchema)]
+pub struct ApiError {
+    /// The HTTP status code.
+    code: u16,
+    /// What went wrong.
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            code: status.as_u16(),
+            message: message.into(),
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(self)).into_response()
+    }
+}
+
+/// Document the [`ApiError`] body as the response of all operations for the other status codes.
+pub fn document_errors(openapi: &mut oasgen::OpenAPI) {
+    let response = oasgen::Response {
+        description: "Error".to_string(),
+        content: [(
+            "application/json".to_string(),
+            oasgen::MediaType {
+                schema: Some(ApiError::schema_ref()),
+                ..Default::default()
+            },
+        )]
+        .into(),
+        ..Default::default()
+    };
+
+    for (_, _, operation) in openapi.operations_mut() {
+        operation.responses.default = Some(oasgen::ReferenceOr::Item(response.clone()));
+    }
+}
+
+#[derive(Debug, Deserialize, OaSchema)]
+pub struct SpecVersionQuery {
+    /// Only return the items recorded while this runtime spec version was live.
+    spec_version: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, OaSchema)]
+pub struct AddressQuery {
+    /// Only return the items of this address, SS58 or hex encoded.
+    address: Option<String>,
+}
+
+impl AddressQuery {
+    /// Returns the parsed address, `400 Bad Request` if it's invalid.
+    fn address(&self) -> Result<Option<Address>, HttpError> {
+        self.address
+            .as_deref()
+            .map(Address::from_str)
+            .transpose()
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))
+    }
+}
 
 #[oasgen]
 pub async fn all_submissions(
-    State((db, _)): State<(Database, PrometheusHandle)>,
-) -> Result<Json<Vec<Submission>>, HttpError> {
-    let submissions = db.get_all_submissions().await.map_err(internal_error)?;
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(address): Query<AddressQuery>,
+    Query(time_range): Query<TimeRange>,
+    Query(SpecVersionQuery { spec_version }): Query<SpecVersionQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Submission>>, HttpError> {
+    let address = address.address()?;
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
+    let submissions = db
+        .get_submissions_paginated(address.as_ref(), &time_range, spec_version, limit, offset)
+        .await
+        .map_err(internal_error)?;
     Ok(Json(submissions))
 }
 
 #[oasgen]
 pub async fn all_success_submissions(
-    State((db, _)): State<(Database, PrometheusHandle)>,
-) -> Result<Json<Vec<Submission>>, HttpError> {
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Submission>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
     let submissions = db
-        .get_all_success_submissions()
+        .get_success_submissions_paginated(limit, offset)
         .await
         .map_err(internal_error)?;
     Ok(Json(submissions))
@@ -37,72 +145,466 @@ pub async fn all_success_submissions(
 
 #[oasgen]
 pub async fn all_failed_submissions(
-    State((db, _)): State<(Database, PrometheusHandle)>,
-) -> Result<Json<Vec<Submission>>, HttpError> {
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Submission>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
     let submissions = db
-        .get_all_failed_submissions()
+        .get_failed_submissions_paginated(limit, offset)
         .await
         .map_err(internal_error)?;
     Ok(Json(submissions))
 }
 
+#[derive(Debug, Deserialize, OaSchema)]
+pub struct WeakSubmissionsQuery {
+    /// Only return the submissions whose solution has a minimal stake below this amount in plancks.
+    below: String,
+}
+
+/// Returns the submissions whose solution has a minimal stake below `below`, the weakest first.
 #[oasgen]
-pub async fn all_unsigned_elections(
-    State((db, _)): State<(Database, PrometheusHandle)>,
-) -> Result<Json<Vec<Election>>, HttpError> {
+pub async fn weak_submissions(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(WeakSubmissionsQuery { below }): Query<WeakSubmissionsQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Submission>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
+    let threshold = below.trim().parse::<u128>().map_err(|e| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid below `{below}`: {e}"),
+        )
+    })?;
+
+    let submissions = db
+        .get_submissions_by_min_stake(threshold, limit, offset)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(submissions))
+}
+
+/// Returns the submissions of each address, keyed by the address as it was sent.
+#[oasgen]
+pub async fn submissions_by_addresses(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Json(addresses): Json<Vec<String>>,
+) -> Result<Json<SubmissionsByAddress>, HttpError> {
+    let mut res = SubmissionsByAddress::default();
+    let mut valid = Vec::new();
+    // The forms each address was sent in, e.g. both as SS58 and hex.
+    let mut sent: HashMap<String, Vec<String>> = HashMap::new();
+
+    for addr in addresses {
+        match Address::from_str(&addr) {
+            Ok(a) => {
+                let forms = sent.entry(a.to_string()).or_default();
+                if forms.is_empty() {
+                    valid.push(a);
+                }
+                if !forms.contains(&addr) {
+                    res.submissions.insert(addr.clone(), Vec::new());
+                    forms.push(addr);
+                }
+            }
+            Err(_) => res.invalid.push(addr),
+        }
+    }
+
+    let submissions = db
+        .get_submissions_by_addresses(&valid)
+        .await
+        .map_err(internal_error)?;
+
+    for submission in submissions {
+        for addr in sent
+            .get(&submission.who().to_string())
+            .into_iter()
+            .flatten()
+        {
+            res.submissions
+                .entry(addr.clone())
+                .or_default()
+                .push(submission.clone());
+        }
+    }
+
+    Ok(Json(res))
+}
+
+#[oasgen]
+pub async fn submission_by_extrinsic(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Path(hash): Path<String>,
+) -> Result<Json<Submission>, HttpError> {
+    let hash = Hash::from_str(hash.trim()).map_err(|e| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid extrinsic hash: {e}"),
+        )
+    })?;
+
+    let submission = db
+        .get_submission_by_extrinsic_hash(hash)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Submission not found"))?;
+    Ok(Json(submission))
+}
+
+/// The validators elected in the given round.
+///
+/// `404 Not Found` if the election of the round or its winners are unknown.
+#[oasgen]
+pub async fn election_winners(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Path(round): Path<u32>,
+) -> Result<Json<Vec<Address>>, HttpError> {
+    let winners = db
+        .get_election_winners(round)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Election winners not found"))?;
+    Ok(Json(winners))
+}
+
+/// Returns the election, submissions and slashes of the round.
+///
+/// `404 Not Found` if nothing was recorded in the round.
+#[oasgen]
+pub async fn round(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Path(round): Path<u32>,
+) -> Result<Json<RoundActivity>, HttpError> {
+    let activity = db
+        .get_round_activity(round)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Round not found"))?;
+    Ok(Json(activity))
+}
+
+/// The number of items the list endpoints return by default.
+const DEFAULT_PAGE_LIMIT: u32 = 100;
+/// The maximum number of items the list endpoints return.
+const MAX_PAGE_LIMIT: u32 = 1000;
+
+#[derive(Debug, Deserialize, OaSchema)]
+pub struct PageQuery {
+    /// The maximum number of items to return, at most 1000.
+    limit: Option<u32>,
+    /// The number of items to skip, defaults to 0.
+    offset: Option<u32>,
+}
+
+impl PageQuery {
+    /// Returns the limit and the offset, `400 Bad Request` if the limit is out of bounds.
+    fn page(&self, default_limit: u32) -> Result<(u32, u32), HttpError> {
+        let limit = self.limit.unwrap_or(default_limit);
+
+        if limit == 0 || limit > MAX_PAGE_LIMIT {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                format!("limit must be between 1 and {MAX_PAGE_LIMIT}"),
+            ));
+        }
+
+        Ok((limit, self.offset.unwrap_or(0)))
+    }
+}
+
+#[oasgen]
+pub async fn elections_with_submissions(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<ElectionWithSubmissions>>, HttpError> {
+    let (limit, offset) = page.page(10)?;
     let elections = db
-        .get_all_unsigned_elections()
+        .get_elections_with_submissions(limit, offset)
         .await
         .map_err(internal_error)?;
     Ok(Json(elections))
 }
 
+#[derive(Debug, Deserialize, OaSchema)]
+pub struct WinRateQuery {
+    /// The number of most recent rounds the address submitted in to look at, defaults to 100.
+    window: Option<u32>,
+}
+
 #[oasgen]
-pub async fn all_elections(
-    State((db, _)): State<(Database, PrometheusHandle)>,
-) -> Result<Json<Vec<Election>>, HttpError> {
-    let winners = db.get_all_elections().await.map_err(internal_error)?;
-    Ok(Json(winners))
+pub async fn win_rate(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Path(address): Path<String>,
+    Query(WinRateQuery { window }): Query<WinRateQuery>,
+) -> Result<Json<WinRate>, HttpError> {
+    let address =
+        Address::from_str(&address).map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+    let window = NonZeroU32::new(window.unwrap_or(100))
+        .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, "window must be non-zero"))?;
+
+    let win_rate = db
+        .get_win_rate(&address, window)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(win_rate))
+}
+
+#[derive(Debug, Deserialize, OaSchema)]
+pub struct SnipesQuery {
+    /// A submission is late if it landed in the final `blocks` blocks of the signed phase, defaults to 10.
+    blocks: Option<u32>,
 }
 
 #[oasgen]
-pub async fn all_failed_elections(
-    State((db, _)): State<(Database, PrometheusHandle)>,
-) -> Result<Json<Vec<Election>>, HttpError> {
+pub async fn snipes(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(SnipesQuery { blocks }): Query<SnipesQuery>,
+) -> Result<Json<Snipes>, HttpError> {
+    let blocks = NonZeroU32::new(blocks.unwrap_or(10))
+        .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, "blocks must be non-zero"))?;
+
+    let snipes = db.get_snipes(blocks).await.map_err(internal_error)?;
+    Ok(Json(snipes))
+}
+
+#[oasgen]
+pub async fn all_unsigned_elections(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Election>>, HttpError> {
+    elections_by_result(&*db, ElectionResultKind::Unsigned, &page).await
+}
+
+#[derive(Debug, Deserialize, OaSchema)]
+pub struct ElectionResultQuery {
+    /// Only return the elections with this result, `signed`, `unsigned`, `failed` or `governance_fallback`.
+    result: Option<String>,
+}
+
+impl ElectionResultQuery {
+    /// Returns the parsed result, `400 Bad Request` if it's unknown.
+    fn result(&self) -> Result<Option<ElectionResultKind>, HttpError> {
+        self.result
+            .as_deref()
+            .map(ElectionResultKind::from_str)
+            .transpose()
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))
+    }
+}
+
+#[oasgen]
+pub async fn all_elections(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(time_range): Query<TimeRange>,
+    Query(SpecVersionQuery { spec_version }): Query<SpecVersionQuery>,
+    Query(result): Query<ElectionResultQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Election>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
     let elections = db
-        .get_all_failed_elections()
+        .get_elections_paginated(&time_range, spec_version, result.result()?, limit, offset)
         .await
         .map_err(internal_error)?;
     Ok(Json(elections))
 }
 
+/// The elections with `result`, the same as `/elections/?result=`.
+async fn elections_by_result(
+    db: &dyn Store,
+    result: ElectionResultKind,
+    page: &PageQuery,
+) -> Result<Json<Page<Election>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
+    let elections = db
+        .get_elections_paginated(&TimeRange::default(), None, Some(result), limit, offset)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(elections))
+}
+
+#[oasgen]
+pub async fn all_failed_elections(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Election>>, HttpError> {
+    elections_by_result(&*db, ElectionResultKind::Failed, &page).await
+}
+
 #[oasgen]
 pub async fn all_signed_elections(
-    State((db, _)): State<(Database, PrometheusHandle)>,
-) -> Result<Json<Vec<Election>>, HttpError> {
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Election>>, HttpError> {
+    elections_by_result(&*db, ElectionResultKind::Signed, &page).await
+}
+
+#[oasgen]
+pub async fn unsigned_despite_signed_elections(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Election>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
     let elections = db
-        .get_all_signed_elections()
+        .get_unsigned_despite_signed_elections_paginated(limit, offset)
         .await
         .map_err(internal_error)?;
     Ok(Json(elections))
 }
 
+#[derive(Debug, Deserialize, OaSchema)]
+pub struct SlashedQuery {
+    /// Only return the slashes of at least this amount in plancks, the largest first.
+    min_amount: Option<String>,
+}
+
+impl SlashedQuery {
+    /// Returns the parsed minimum amount, `400 Bad Request` if it's invalid.
+    fn min_amount(&self) -> Result<Option<u128>, HttpError> {
+        self.min_amount
+            .as_deref()
+            .map(|min_amount| {
+                min_amount.trim().parse::<u128>().map_err(|e| {
+                    ApiError::new(
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid min_amount `{min_amount}`: {e}"),
+                    )
+                })
+            })
+            .transpose()
+    }
+}
+
 #[oasgen]
 pub async fn all_slashed(
-    State((db, _)): State<(Database, PrometheusHandle)>,
-) -> Result<Json<Vec<Slashed>>, HttpError> {
-    let slashed = db.get_all_slashed().await.map_err(internal_error)?;
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(slashed): Query<SlashedQuery>,
+    Query(address): Query<AddressQuery>,
+    Query(time_range): Query<TimeRange>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Slashed>>, HttpError> {
+    let address = address.address()?;
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
+    let min_amount = slashed.min_amount()?;
+
+    let slashed = db
+        .get_slashed_paginated(address.as_ref(), min_amount, &time_range, limit, offset)
+        .await
+        .map_err(internal_error)?;
     Ok(Json(slashed))
 }
 
+#[derive(Debug, Serialize, OaSchema)]
+pub struct Count {
+    count: u64,
+}
+
+/// Returns the number of submissions with the same filters as `/submissions/`.
+#[oasgen]
+pub async fn count_submissions(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(address): Query<AddressQuery>,
+    Query(time_range): Query<TimeRange>,
+    Query(SpecVersionQuery { spec_version }): Query<SpecVersionQuery>,
+) -> Result<Json<Count>, HttpError> {
+    let address = address.address()?;
+    let count = db
+        .count_submissions(address.as_ref(), &time_range, spec_version)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(Count { count }))
+}
+
+/// Returns the number of elections with the same filters as `/elections/`.
+#[oasgen]
+pub async fn count_elections(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(time_range): Query<TimeRange>,
+    Query(SpecVersionQuery { spec_version }): Query<SpecVersionQuery>,
+) -> Result<Json<Count>, HttpError> {
+    let count = db
+        .count_elections(&time_range, spec_version)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(Count { count }))
+}
+
+/// Returns the number of slashes with the same filters as `/slashed/`.
+#[oasgen]
+pub async fn count_slashed(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(slashed): Query<SlashedQuery>,
+    Query(address): Query<AddressQuery>,
+    Query(time_range): Query<TimeRange>,
+) -> Result<Json<Count>, HttpError> {
+    let address = address.address()?;
+    let count = db
+        .count_slashed(address.as_ref(), slashed.min_amount()?, &time_range)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(Count { count }))
+}
+
+#[oasgen]
+pub async fn all_ejections(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Ejection>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
+    let ejections = db
+        .get_ejections_paginated(limit, offset)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(ejections))
+}
+
+#[oasgen]
+pub async fn all_config_changes(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<ConfigChange>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
+    let changes = db
+        .get_config_changes_paginated(limit, offset)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(changes))
+}
+
+#[derive(Debug, Deserialize, OaSchema)]
+pub struct SortQuery {
+    /// The column to pick the most recent items by and to sort them by, `round` (default) or `block`.
+    sort: Option<String>,
+    /// The order to return the items in, `desc` (default, the most recent first) or `asc`.
+    order: Option<String>,
+}
+
+impl SortQuery {
+    /// Returns the parsed sort, `400 Bad Request` if it's invalid.
+    fn sort(&self) -> Result<Sort, HttpError> {
+        let column = self.sort.as_deref().map(str::parse).transpose();
+        let order = self.order.as_deref().map(str::parse).transpose();
+
+        Ok(Sort {
+            column: column
+                .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?
+                .unwrap_or_default(),
+            order: order
+                .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?
+                .unwrap_or_default(),
+        })
+    }
+}
+
 #[oasgen]
 pub async fn most_recent_submissions(
-    State((db, _)): State<(Database, PrometheusHandle)>,
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
     Path(n): Path<usize>,
+    Query(sort): Query<SortQuery>,
 ) -> Result<Json<Vec<Submission>>, HttpError> {
     let n = into_non_zero_usize(n)?;
     let submissions = db
-        .get_most_recent_submissions(n)
+        .get_most_recent_submissions(n, sort.sort()?)
         .await
         .map_err(internal_error)?;
     Ok(Json(submissions))
@@ -110,12 +612,13 @@ pub async fn most_recent_submissions(
 
 #[oasgen]
 pub async fn most_recent_elections(
-    State((db, _)): State<(Database, PrometheusHandle)>,
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
     Path(n): Path<usize>,
+    Query(sort): Query<SortQuery>,
 ) -> Result<Json<Vec<Election>>, HttpError> {
     let n = into_non_zero_usize(n)?;
     let winners = db
-        .get_most_recent_elections(n)
+        .get_most_recent_elections(n, sort.sort()?)
         .await
         .map_err(internal_error)?;
     Ok(Json(winners))
@@ -123,40 +626,534 @@ pub async fn most_recent_elections(
 
 #[oasgen]
 pub async fn most_recent_slashed(
-    State((db, _)): State<(Database, PrometheusHandle)>,
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
     Path(n): Path<usize>,
+    Query(sort): Query<SortQuery>,
 ) -> Result<Json<Vec<Slashed>>, HttpError> {
     let n = into_non_zero_usize(n)?;
     let slashed = db
-        .get_most_recent_slashed(n)
+        .get_most_recent_slashed(n, sort.sort()?)
         .await
         .map_err(internal_error)?;
     Ok(Json(slashed))
 }
 
+/// The state shared by all routes.
+#[derive(Clone)]
+pub struct AppState {
+    pub db: SharedStore,
+    pub prometheus: PrometheusHandle,
+    pub client: Client,
+    /// The maximum age of the most recent processed block for `/ready`.
+    pub max_healthy_lag: Option<Duration>,
+}
+
+impl FromRef<AppState> for (SharedStore, PrometheusHandle) {
+    fn from_ref(state: &AppState) -> Self {
+        (state.db.clone(), state.prometheus.clone())
+    }
+}
+
+#[derive(Debug, Serialize, OaSchema)]
+pub struct BestScore {
+    /// The finalized block the signed queue was read at.
+    block: u32,
+    /// The submitter of the best queued solution.
+    address: Address,
+    /// The block the best queued solution was submitted in.
+    submitted_at: u32,
+    score: Score,
+}
+
+/// Returns the best solution currently queued in the signed phase at the latest finalized block,
+/// `404 Not Found` if the queue is empty.
+#[oasgen]
+pub async fn best_score(State(state): State<AppState>) -> Result<Json<BestScore>, HttpError> {
+    let rpc = state.client.rpc();
+    let hash = rpc
+        .chain_get_finalized_head()
+        .await
+        .map_err(internal_error)?;
+    let header = rpc
+        .chain_get_header(Some(hash))
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| internal_error(format!("Finalized block {hash:?} not found")))?;
+
+    let queue = SignedQueue::fetch(&state.client, hash)
+        .await
+        .map_err(internal_error)?;
+    let (score, submitted_at, address) = queue
+        .best()
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "No solution queued"))?;
+
+    Ok(Json(BestScore {
+        block: header.number,
+        address: address.clone(),
+        submitted_at: *submitted_at,
+        score: (*score).into(),
+    }))
+}
+
+/// Liveness probe, always `200 OK` while the process serves requests.
+///
+/// Unlike `/ready` this doesn't check the database or the node.
+#[oasgen]
+pub async fn health() -> String {
+    "OK".to_string()
+}
+
+#[derive(Debug, Serialize, OaSchema)]
+pub struct Readiness {
+    /// The number of seconds the most recent processed block is behind the wall clock.
+    lag_secs: Option<u64>,
+}
+
+/// Returns `503 Service Unavailable` if the database or the node is unreachable
+/// or if the block processing lags behind more than `--max-healthy-lag-seconds`.
+#[oasgen]
+pub async fn ready(State(state): State<AppState>) -> Result<Json<Readiness>, HttpError> {
+    let unavailable = |msg: String| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, msg);
+
+    state
+        .db
+        .ping()
+        .await
+        .map_err(|e| unavailable(format!("Database unavailable: {e}")))?;
+    state
+        .client
+        .rpc()
+        .system_health()
+        .await
+        .map_err(|e| unavailable(format!("Node unavailable: {e}")))?;
+
+    let lag = ingestion::lag();
+
+    if let Some(max) = state.max_healthy_lag {
+        match lag {
+            Some(lag) if lag > max => {
+                return Err(unavailable(format!(
+                    "Lagging {}s behind, more than the maximum of {}s",
+                    lag.as_secs(),
+                    max.as_secs()
+                )));
+            }
+            Some(_) => {}
+            None => return Err(unavailable("No block processed yet".to_string())),
+        }
+    }
+
+    Ok(Json(Readiness {
+        lag_secs: lag.map(|l| l.as_secs()),
+    }))
+}
+
 #[oasgen]
-pub async fn metrics(State((_, prometheus)): State<(Database, PrometheusHandle)>) -> String {
+pub async fn metrics(State((_, prometheus)): State<(SharedStore, PrometheusHandle)>) -> String {
     prometheus.render()
 }
 
 #[oasgen]
 pub async fn stats(
-    State((db, _)): State<(Database, PrometheusHandle)>,
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
 ) -> Result<Json<Stats>, HttpError> {
     let stats = db.get_stats().await.map_err(internal_error)?;
     Ok(Json(stats))
 }
 
+#[oasgen]
+pub async fn latency() -> Json<Latency> {
+    Json(ingestion::latency())
+}
+
+#[oasgen]
+pub async fn miner_distribution(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<MinerDistribution>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
+    let distribution = db
+        .get_miner_distribution(limit, offset)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(distribution))
+}
+
+/// The submission counts and won elections of every signed miner, the most successful first.
+#[oasgen]
+pub async fn miners(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(time_range): Query<TimeRange>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<MinerStats>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
+    let miners = db
+        .get_miner_stats(None, &time_range, limit, offset)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(miners))
+}
+
+/// The submission counts and won elections of one signed miner.
+///
+/// `404 Not Found` if the miner has no submissions.
+#[oasgen]
+pub async fn miner(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Path(address): Path<String>,
+    Query(time_range): Query<TimeRange>,
+) -> Result<Json<MinerStats>, HttpError> {
+    let address =
+        Address::from_str(&address).map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+    let miner = db
+        .get_miner_stats(Some(&address), &time_range, 1, 0)
+        .await
+        .map_err(internal_error)?
+        .items
+        .pop()
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Miner not found"))?;
+    Ok(Json(miner))
+}
+
+#[oasgen]
+pub async fn never_successful_miners(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<NeverSuccessfulMiner>>, HttpError> {
+    let (limit, offset) = page.page(DEFAULT_PAGE_LIMIT)?;
+    let miners = db
+        .get_never_successful_miners(limit, offset)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(miners))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Comma-separated event types to subscribe to, defaults to all types.
+    types: Option<String>,
+}
+
+/// Server-sent events of the rows inserted into the database.
+///
+/// This isn't part of the OpenAPI spec because SSE is not supported by it.
+pub async fn events_stream(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(EventsQuery { types }): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, HttpError> {
+    let mut kinds = match types {
+        Some(types) => types
+            .split(',')
+            .filter(|t| !t.trim().is_empty())
+            .map(EventKind::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?,
+        None => Vec::new(),
+    };
+    if kinds.is_empty() {
+        kinds = EventKind::ALL.to_vec();
+    }
+
+    Ok(event_stream(&*db, kinds))
+}
+
+/// Server-sent events of the submissions inserted into the database.
+pub async fn submissions_stream(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    event_stream(&*db, vec![EventKind::Submission])
+}
+
+/// Server-sent events of the elections inserted into the database, including failed elections.
+pub async fn elections_stream(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    event_stream(&*db, vec![EventKind::Election, EventKind::ElectionFailed])
+}
+
+/// Stream the inserted rows of the given kinds from now on.
+///
+/// A client that is too slow to keep up misses the events it lagged behind on,
+/// the database inserts are never blocked by it.
+fn event_stream(
+    db: &dyn Store,
+    kinds: Vec<EventKind>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let stream = futures::stream::unfold((db.subscribe(), kinds), |(mut rx, kinds)| async move {
+        loop {
+            let event = match rx.recv().await {
+                Ok(event) if kinds.contains(&event.kind()) => event,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(n)) => {
+                    tracing::warn!(target: LOG_TARGET, "events stream lagged, skipped {n} events");
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            };
+
+            match SseEvent::default()
+                .event(event.kind().as_str())
+                .json_data(&event)
+            {
+                Ok(sse) => return Some((Ok(sse), (rx, kinds))),
+                Err(e) => {
+                    tracing::warn!(target: LOG_TARGET, "failed to serialize event: {e}");
+                }
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// The number of rows read from the database at a time for the CSV endpoints.
+const CSV_CHUNK_SIZE: u32 = 1000;
+
+/// The submissions as CSV with the score flattened into columns, filtered like `/submissions/`.
+///
+/// The rows are streamed, so unlike the JSON endpoints `limit` defaults to all rows.
+/// This isn't part of the OpenAPI spec, which only describes JSON responses.
+pub async fn submissions_csv(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(address): Query<AddressQuery>,
+    Query(time_range): Query<TimeRange>,
+    Query(SpecVersionQuery { spec_version }): Query<SpecVersionQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Response, HttpError> {
+    let address = address.address()?;
+
+    Ok(csv_stream(page, move |after, limit, offset| {
+        let (db, address, time_range) = (db.clone(), address.clone(), time_range.clone());
+        async move {
+            db.get_submissions_after(
+                address.as_ref(),
+                &time_range,
+                spec_version,
+                after,
+                limit,
+                offset,
+            )
+            .await
+        }
+    }))
+}
+
+/// The elections as CSV with the score flattened into columns, filtered like `/elections/`.
+///
+/// The rows are streamed, so unlike the JSON endpoints `limit` defaults to all rows.
+/// This isn't part of the OpenAPI spec, which only describes JSON responses.
+pub async fn elections_csv(
+    State((db, _)): State<(SharedStore, PrometheusHandle)>,
+    Query(time_range): Query<TimeRange>,
+    Query(SpecVersionQuery { spec_version }): Query<SpecVersionQuery>,
+    Query(page): Query<PageQuery>,
+) -> Response {
+    csv_stream(page, move |after, limit, offset| {
+        let (db, time_range) = (db.clone(), time_range.clone());
+        async move {
+            db.get_elections_after(&time_range, spec_version, after, limit, offset)
+                .await
+        }
+    })
+}
+
+/// Stream the rows returned by `fetch(after, limit, offset)` as CSV, reading them in chunks
+/// by id so that neither the table nor a long-running query is held in memory.
+///
+/// A failure midway ends the response early, since the status was sent already.
+fn csv_stream<T, F, Fut>(page: PageQuery, fetch: F) -> Response
+where
+    T: Serialize,
+    F: Fn(i32, u32, u32) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = Result<Vec<(i32, T)>, db::Error>> + Send,
+{
+    let state = (fetch, 0, page.offset.unwrap_or(0), page.limit, None);
+    let stream = futures::stream::try_unfold(
+        state,
+        |(fetch, after, offset, remaining, columns): (
+            F,
+            i32,
+            u32,
+            Option<u32>,
+            Option<Vec<String>>,
+        )| async move {
+            let limit = remaining.map_or(CSV_CHUNK_SIZE, |r| r.min(CSV_CHUNK_SIZE));
+            if limit == 0 {
+                return Ok(None);
+            }
+
+            let rows = fetch(after, limit, offset).await.inspect_err(|e| {
+                tracing::warn!(target: LOG_TARGET, "Failed to read the CSV rows: {e}");
+            })?;
+            let Some(&(last, _)) = rows.last() else {
+                return Ok(None);
+            };
+            let remaining = remaining.map(|r| r - rows.len() as u32);
+
+            let rows = rows
+                .into_iter()
+                .map(|(_, row)| serde_json::to_value(row).map(|row| export::flatten(row, "score")))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut chunk = Vec::new();
+            let columns = match columns {
+                Some(columns) => columns,
+                None => {
+                    let columns = export::csv_columns(&rows[0]);
+                    export::write_csv_header(&mut chunk, &columns)?;
+                    columns
+                }
+            };
+            for row in &rows {
+                export::write_csv_row(&mut chunk, &columns, row)?;
+            }
+
+            anyhow::Ok(Some((chunk, (fetch, last, 0, remaining, Some(columns)))))
+        },
+    );
+
+    (
+        [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
 // Convert a usize into a NonZeroUsize, returning an error if the value is zero.
 //
 // oasgen doesn't support NonZero types yet, so we have to do this manually.
 fn into_non_zero_usize(value: usize) -> Result<NonZeroUsize, HttpError> {
-    NonZeroUsize::new(value).ok_or_else(|| {
+    NonZeroUsize::new(value)
+        .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, "path param value must be non-zero"))
+}
+
+/// The key of `--api-key`, which is redacted in the debug output.
+#[derive(Clone)]
+pub struct ApiKey(Arc<str>);
+
+impl FromStr for ApiKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("The API key must not be empty".to_string());
+        }
+        Ok(Self(s.into()))
+    }
+}
+
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ApiKey(***)")
+    }
+}
+
+impl ApiKey {
+    /// Compares in constant time for keys of the same length, so the key can't be guessed byte by byte.
+    fn matches(&self, key: &[u8]) -> bool {
+        let expected = self.0.as_bytes();
+        expected.len() == key.len()
+            && expected
+                .iter()
+                .zip(key)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// The API key and whether the docs require it too.
+#[derive(Clone, Debug)]
+pub struct ApiAuth {
+    pub key: ApiKey,
+    pub docs_require_auth: bool,
+}
+
+/// Requires the API key as `Authorization: Bearer <key>` or `X-API-Key: <key>`,
+/// except for `/health` and, unless `docs_require_auth` is set, the docs,
+/// and returns `401 Unauthorized` otherwise.
+pub async fn api_key(State(auth): State<ApiAuth>, req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    if path == "/health" || (path.starts_with("/docs/") && !auth.docs_require_auth) {
+        return next.run(req).await;
+    }
+    let expected = &auth.key;
+
+    let headers = req.headers();
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.as_bytes().strip_prefix(b"Bearer "));
+    let key = bearer.or_else(|| headers.get("x-api-key").map(|v| v.as_bytes()));
+
+    if key.is_some_and(|key| expected.matches(key)) {
+        next.run(req).await
+    } else {
         (
-            StatusCode::BAD_REQUEST,
-            "path param value must be non-zero".to_string(),
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            ApiError::new(StatusCode::UNAUTHORIZED, "Missing or invalid API key"),
         )
-    })
+            .into_response()
+    }
+}
+
+/// Records the number of requests and the response time per route, except for `/metrics`.
+pub async fn http_metrics(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string());
+
+    let Some(route) = route.filter(|r| r != "/metrics") else {
+        return next.run(req).await;
+    };
+
+    let now = Instant::now();
+    let res = next.run(req).await;
+    prometheus::record_http_request(&route, res.status().as_u16(), now.elapsed());
+
+    res
+}
+
+/// Adds a weak `ETag` derived from the body to successful JSON responses of `GET` requests
+/// and returns `304 Not Modified` without a body if it matches `If-None-Match`.
+pub async fn etag(req: Request, next: Next) -> Response {
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+    let is_get = req.method() == Method::GET;
+    let res = next.run(req).await;
+
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if !is_get || !is_json || !res.status().is_success() {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return internal_error(e).into_response(),
+    };
+
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    let etag = format!("W/\"{:016x}\"", hasher.finish());
+
+    let matches = if_none_match
+        .as_ref()
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag == etag)
+        });
+
+    let etag = header::HeaderValue::from_str(&etag).expect("Hex is a valid header value; qed");
+    if matches {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    parts.headers.insert(header::ETAG, etag);
+    Response::from_parts(parts, Body::from(bytes))
 }
 
 /// Utility function for mapping any error into a `500 Internal Server Error`
@@ -165,5 +1162,98 @@ fn internal_error<E>(err: E) -> HttpError
 where
     E: std::fmt::Display,
 {
-    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory::MemoryStore;
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use polkadot_sdk::sp_npos_elections::ElectionScore;
+
+    fn state(db: MemoryStore) -> State<(SharedStore, PrometheusHandle)> {
+        let prometheus = PrometheusBuilder::new().build_recorder().handle();
+        State((Arc::new(db), prometheus))
+    }
+
+    async fn submit(db: &MemoryStore, who: &Address, round: u32, success: bool) {
+        db.insert_submission(Submission::new(
+            who.clone(),
+            round,
+            round * 10,
+            ElectionScore::default(),
+            success,
+        ))
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn submissions_of_an_address() {
+        let (miner, other) = (Address::from_bytes(&[1; 32]), Address::from_bytes(&[2; 32]));
+        let db = MemoryStore::default();
+        submit(&db, &miner, 1, true).await;
+        submit(&db, &other, 1, false).await;
+        submit(&db, &miner, 2, false).await;
+
+        let Json(page) = all_submissions(
+            state(db),
+            Query(AddressQuery {
+                address: Some(miner.to_string()),
+            }),
+            Query(TimeRange::default()),
+            Query(SpecVersionQuery { spec_version: None }),
+            Query(PageQuery {
+                limit: Some(1),
+                offset: Some(1),
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(page.total, 2);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].who(), &miner);
+    }
+
+    #[tokio::test]
+    async fn miner_stats() {
+        let who = Address::from_bytes(&[1; 32]);
+        let db = MemoryStore::default();
+        submit(&db, &who, 1, true).await;
+        submit(&db, &who, 2, false).await;
+        db.insert_election(Election::new(
+            crate::types::ElectionResult::Signed(vec![who.clone()]),
+            1,
+            15,
+            ElectionScore::default(),
+        ))
+        .await
+        .unwrap();
+        let state = state(db);
+
+        let Json(stats) = miner(
+            state.clone(),
+            Path(who.to_string()),
+            Query(TimeRange::default()),
+        )
+        .await
+        .unwrap();
+        let stats = serde_json::to_value(stats).unwrap();
+        assert_eq!(stats["submissions"], 2);
+        assert_eq!(stats["won"], 1);
+        assert_eq!(stats["latest_round"], 2);
+        assert_eq!(stats["latest_round_result"], "lost");
+        assert_eq!(stats["prev_round_result"], "won");
+
+        let err = miner(
+            state,
+            Path(Address::from_bytes(&[2; 32]).to_string()),
+            Query(TimeRange::default()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+    }
 }
@@ -0,0 +1,160 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use std::time::Duration;
+
+use crate::{redact_password, LOG_TARGET};
+use futures::FutureExt;
+use subxt::backend::rpc::reconnecting_rpc_client::{self, ExponentialBackoff};
+use subxt::backend::rpc::{RawRpcFuture, RawRpcSubscription, RawValue, RpcClientT};
+use subxt::ext::subxt_rpcs::Error;
+use tokio::sync::RwLock;
+use url::Url;
+
+/// The number of attempts to reconnect to an endpoint before switching to the next one,
+/// which takes about 40 seconds with the exponential backoff.
+const RECONNECT_ATTEMPTS: usize = 10;
+
+/// An RPC client that reconnects to its endpoint, and if that keeps failing
+/// switches to the next of several endpoints.
+///
+/// With a single endpoint, it keeps reconnecting to it forever.
+pub struct FailoverRpcClient {
+    urls: Vec<Url>,
+    /// The index of the endpoint in use and its client.
+    active: RwLock<(usize, reconnecting_rpc_client::RpcClient)>,
+}
+
+impl FailoverRpcClient {
+    /// Connect to the first of `urls` that is reachable.
+    pub async fn new(urls: Vec<Url>) -> anyhow::Result<Self> {
+        for (i, url) in urls.iter().enumerate() {
+            match connect(url, reconnect_attempts(&urls)).await {
+                Ok(client) => {
+                    return Ok(Self {
+                        active: RwLock::new((i, client)),
+                        urls,
+                    })
+                }
+                Err(e) => {
+                    tracing::warn!(target: LOG_TARGET, "Failed to connect to {}: {e}", redact_password(url));
+                }
+            }
+        }
+
+        anyhow::bail!("None of the RPC endpoints are reachable")
+    }
+
+    /// Switch to the next reachable endpoint after `failed`, unless another request did already.
+    async fn failover(&self, failed: usize) -> Result<(), Error> {
+        let mut active = self.active.write().await;
+        if active.0 != failed {
+            return Ok(());
+        }
+
+        for offset in 1..=self.urls.len() {
+            let i = (failed + offset) % self.urls.len();
+            let url = &self.urls[i];
+
+            match connect(url, reconnect_attempts(&self.urls)).await {
+                Ok(client) => {
+                    tracing::warn!(
+                        target: LOG_TARGET,
+                        "Lost the connection to {}, switched to {}",
+                        redact_password(&self.urls[failed]),
+                        redact_password(url)
+                    );
+                    *active = (i, client);
+                    return Ok(());
+                }
+                Err(e) => {
+                    tracing::warn!(target: LOG_TARGET, "Failed to connect to {}: {e}", redact_password(url));
+                }
+            }
+        }
+
+        Err(Error::Client(
+            "None of the RPC endpoints are reachable".into(),
+        ))
+    }
+
+    async fn active(&self) -> (usize, reconnecting_rpc_client::RpcClient) {
+        self.active.read().await.clone()
+    }
+}
+
+/// With several endpoints, give up reconnecting after a number of attempts to switch to the next one.
+fn reconnect_attempts(urls: &[Url]) -> usize {
+    if urls.len() > 1 {
+        RECONNECT_ATTEMPTS
+    } else {
+        usize::MAX
+    }
+}
+
+async fn connect(url: &Url, attempts: usize) -> anyhow::Result<reconnecting_rpc_client::RpcClient> {
+    let client = reconnecting_rpc_client::RpcClient::builder()
+        .max_request_size(u32::MAX)
+        .max_response_size(u32::MAX)
+        .retry_policy(
+            ExponentialBackoff::from_millis(100)
+                .max_delay(Duration::from_secs(10))
+                .take(attempts),
+        )
+        .request_timeout(Duration::from_secs(600))
+        .build(url.as_str())
+        .await?;
+
+    tracing::info!(target: LOG_TARGET, "Using the RPC endpoint {}", redact_password(url));
+
+    Ok(client)
+}
+
+/// Whether the client stopped reconnecting, requests to it fail with `Dropped` from then on.
+fn is_dropped(err: &Error) -> bool {
+    match err {
+        Error::Client(e) => matches!(
+            e.downcast_ref::<reconnecting_rpc_client::Error>(),
+            Some(reconnecting_rpc_client::Error::Dropped)
+        ),
+        _ => false,
+    }
+}
+
+impl RpcClientT for FailoverRpcClient {
+    fn request_raw<'a>(
+        &'a self,
+        method: &'a str,
+        params: Option<Box<RawValue>>,
+    ) -> RawRpcFuture<'a, Box<RawValue>> {
+        async move {
+            loop {
+                let (i, client) = self.active().await;
+                match client.request_raw(method, params.clone()).await {
+                    Err(e) if is_dropped(&e) => self.failover(i).await?,
+                    res => return res,
+                }
+            }
+        }
+        .boxed()
+    }
+
+    fn subscribe_raw<'a>(
+        &'a self,
+        sub: &'a str,
+        params: Option<Box<RawValue>>,
+        unsub: &'a str,
+    ) -> RawRpcFuture<'a, RawRpcSubscription> {
+        async move {
+            loop {
+                let (i, client) = self.active().await;
+                match client.subscribe_raw(sub, params.clone(), unsub).await {
+                    Err(e) if is_dropped(&e) => self.failover(i).await?,
+                    res => return res,
+                }
+            }
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::LOG_TARGET;
+use hyper_util::rt::TokioIo;
+use hyper_util::service::TowerToHyperService;
+use tokio::net::TcpListener;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+/// Load the PEM encoded certificate chain and private key.
+pub fn load_config(cert: &Path, key: &Path) -> anyhow::Result<Arc<ServerConfig>> {
+    let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(std::fs::File::open(cert)?))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("Invalid certificate {}: {e}", cert.display()))?;
+    let key = rustls_pemfile::private_key(&mut std::io::BufReader::new(std::fs::File::open(key)?))
+        .map_err(|e| anyhow::anyhow!("Invalid private key {}: {e}", key.display()))?
+        .ok_or_else(|| anyhow::anyhow!("No private key found in {}", key.display()))?;
+
+    let config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?;
+
+    Ok(Arc::new(config))
+}
+
+/// Serve `app` over HTTPS with the `config` loaded from `cert` and `key` until `shutdown` completes.
+///
+/// The certificate and the private key are reloaded on `SIGHUP`, connections that
+/// are already established keep using the previous certificate.
+pub async fn serve(
+    listener: TcpListener,
+    app: axum::Router,
+    (cert, key): (PathBuf, PathBuf),
+    config: Arc<ServerConfig>,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    let config = Arc::new(RwLock::new(config));
+
+    let reload = config.clone();
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match load_config(&cert, &key) {
+                Ok(new) => {
+                    *reload.write().unwrap_or_else(|e| e.into_inner()) = new;
+                    tracing::info!(target: LOG_TARGET, "Reloaded the TLS certificate");
+                }
+                Err(e) => {
+                    tracing::error!(target: LOG_TARGET, "Failed to reload the TLS certificate, keeping the previous one: {e}");
+                }
+            }
+        }
+    });
+
+    tokio::pin!(shutdown);
+
+    loop {
+        let (stream, addr) = tokio::select! {
+            _ = &mut shutdown => return Ok(()),
+            res = listener.accept() => match res {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::warn!(target: LOG_TARGET, "Failed to accept a connection: {e}");
+                    continue;
+                }
+            },
+        };
+
+        let acceptor = TlsAcceptor::from(config.read().unwrap_or_else(|e| e.into_inner()).clone());
+        let service = TowerToHyperService::new(app.clone());
+
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    tracing::debug!(target: LOG_TARGET, "TLS handshake with {addr} failed: {e}");
+                    return;
+                }
+            };
+
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!(target: LOG_TARGET, "Connection with {addr} failed: {e}");
+            }
+        });
+    }
+}
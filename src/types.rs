@@ -30,37 +30,40 @@ pub use subxt::config::Header as HeaderT;
 pub type ExtrinsicDetails = subxt::blocks::ExtrinsicDetails<subxt::PolkadotConfig, ChainClient>;
 
 use oasgen::OaSchema;
+use polkadot_sdk::sp_core::crypto::{AccountId32, Ss58AddressFormat, Ss58Codec};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::str::FromStr;
-use subxt::{backend::rpc::reconnecting_rpc_client::ExponentialBackoff, utils::H256};
+use std::{collections::BTreeMap, str::FromStr, sync::OnceLock};
+use subxt::utils::H256;
 use url::Url;
 
+use crate::{rpc::FailoverRpcClient, LOG_TARGET};
+
 pub const EPM_PALLET_NAME: &str = "ElectionProviderMultiPhase";
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 struct ActiveRound {
     round: u32,
     start_block: u64,
     last_block: u64,
+    /// The hashes of the blocks processed in this round.
+    hashes: BTreeMap<u64, Hash>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub enum ElectionResult {
-    // Signed submission was granted as winner
-    Signed(Address),
+    // Signed submissions were rewarded, usually a single one.
+    Signed(Vec<Address>),
     // Election failed i.e, no winner was selected
     Failed,
     // No signed solution was submitted and the election was finalized offchain.
     //
     // There is no event for this and if the election is finalized without a reward
     // then the election was finalized by offchain solution.
+    #[default]
     Unsigned,
-}
-
-impl Default for ElectionResult {
-    fn default() -> Self {
-        Self::Unsigned
-    }
+    // The signed and unsigned phases failed and the election was finalized
+    // by a fallback or an emergency solution, such as one provided by governance.
+    GovernanceFallback,
 }
 
 impl std::fmt::Display for ElectionResult {
@@ -69,15 +72,71 @@ impl std::fmt::Display for ElectionResult {
             Self::Signed(_) => f.write_str("signed"),
             Self::Failed => f.write_str("failed"),
             Self::Unsigned => f.write_str("unsigned"),
+            Self::GovernanceFallback => f.write_str("governance fallback"),
         }
     }
 }
 
+/// Why a submission wasn't stored.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FailureReason {
+    // The solution was computed against the snapshot of a previous round.
+    StaleSnapshot,
+    // The score isn't good enough, e.g. below the minimum untrusted score or the queued solution.
+    LowScore,
+    // The signed queue is full and the solution isn't better than the weakest queued one.
+    QueueFull,
+    // The solution was submitted in a phase that doesn't accept it.
+    WrongPhase,
+    // Any other reason, e.g. a wrong winner count or the deposit couldn't be paid.
+    Invalid,
+}
+
+impl FailureReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::StaleSnapshot => "stale_snapshot",
+            Self::LowScore => "low_score",
+            Self::QueueFull => "queue_full",
+            Self::WrongPhase => "wrong_phase",
+            Self::Invalid => "invalid",
+        }
+    }
+
+    /// Classify the name of the EPM error that the submission failed with.
+    pub fn from_error(name: &str) -> Self {
+        match name {
+            "PreDispatchDifferentRound" | "OcwCallWrongEra" => Self::StaleSnapshot,
+            "PreDispatchWeakSubmission" => Self::LowScore,
+            "SignedQueueFull" => Self::QueueFull,
+            "PreDispatchEarlySubmission" | "CallNotAllowed" => Self::WrongPhase,
+            _ => Self::Invalid,
+        }
+    }
+}
+
+impl std::fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Represents the state of an election round which needs be reset after the election is finalized.
-#[derive(Debug)]
+///
+/// The monitor stores it in the database after every block to resume the round after a restart.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ElectionRound {
     result: ElectionResult,
     inner: Option<ActiveRound>,
+    /// The number of signed solutions submitted in the round so far, stored or not.
+    #[serde(default)]
+    signed_submissions: u32,
+}
+
+impl Default for ElectionRound {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ElectionRound {
@@ -85,6 +144,7 @@ impl ElectionRound {
         Self {
             result: ElectionResult::Unsigned,
             inner: None,
+            signed_submissions: 0,
         }
     }
 
@@ -92,7 +152,11 @@ impl ElectionRound {
         self.inner.is_some()
     }
 
-    pub fn new_block(&mut self, block: u64, round: u32) {
+    /// Record a processed block.
+    ///
+    /// Returns the hash of the block that was processed before at the same height if it differs,
+    /// which means that the chain was reorganized.
+    pub fn new_block(&mut self, block: u64, hash: Hash, round: u32) -> Option<Hash> {
         let state = match self.inner.as_mut() {
             Some(state) => state,
             None => {
@@ -100,19 +164,43 @@ impl ElectionRound {
                     round,
                     start_block: block,
                     last_block: block,
+                    hashes: BTreeMap::from([(block, hash)]),
                 });
-                return;
+                return None;
             }
         };
 
+        let prev = state.hashes.insert(block, hash).filter(|h| *h != hash);
+
         // ElectionFinalized is emitted in the next round
         // so we need to wait for it to be emitted before
         // clearing the state and starting a new round.
         //
         // However
         if round == state.round {
-            state.last_block = block;
+            // Skipped blocks are processed later, out of order.
+            state.start_block = state.start_block.min(block);
+            state.last_block = state.last_block.max(block);
         }
+
+        prev
+    }
+
+    /// Whether `block` was processed in the active round with the same `hash`.
+    pub fn is_processed(&self, block: u64, hash: Hash) -> bool {
+        self.hash(block) == Some(hash)
+    }
+
+    /// The hash `block` was processed with in the active round, `None` if it wasn't processed.
+    pub fn hash(&self, block: u64) -> Option<Hash> {
+        self.inner
+            .as_ref()
+            .and_then(|s| s.hashes.get(&block).copied())
+    }
+
+    /// The round of the first processed block, `None` if no round is active.
+    pub fn round(&self) -> Option<u32> {
+        self.inner.as_ref().map(|s| s.round)
     }
 
     pub fn first_block(&self) -> Option<u64> {
@@ -122,23 +210,60 @@ impl ElectionRound {
     pub fn clear(&mut self) {
         self.result = ElectionResult::default();
         self.inner = None;
+        self.signed_submissions = 0;
+    }
+
+    /// Record a signed solution submitted in the round.
+    pub fn add_signed_submission(&mut self) {
+        self.signed_submissions += 1;
     }
 
-    pub fn set_winner(&mut self, winner: Address) {
-        assert!(matches!(self.result, ElectionResult::Unsigned));
-        self.result = ElectionResult::Signed(winner);
+    pub fn signed_submissions(&self) -> u32 {
+        self.signed_submissions
     }
 
+    /// Record a rewarded signed submission, all rewards of the round are kept.
+    pub fn add_winner(&mut self, winner: Address) {
+        match &mut self.result {
+            ElectionResult::Signed(winners) => winners.push(winner),
+            _ => self.result = ElectionResult::Signed(vec![winner]),
+        }
+    }
+
+    /// The election failed, which is only expected while no other result was recorded.
     pub fn election_failed(&mut self) {
-        assert!(matches!(self.result, ElectionResult::Unsigned));
+        if !matches!(self.result, ElectionResult::Unsigned) {
+            tracing::warn!(
+                target: LOG_TARGET,
+                "Election failed after the election result was already {}, overriding it",
+                self.result
+            );
+        }
         self.result = ElectionResult::Failed;
     }
 
+    /// The election was finalized by a fallback solution, which may happen after
+    /// the election already failed.
+    pub fn governance_fallback(&mut self) {
+        if !matches!(
+            self.result,
+            ElectionResult::Unsigned | ElectionResult::Failed
+        ) {
+            tracing::warn!(
+                target: LOG_TARGET,
+                "Governance fallback after the election result was already {}, overriding it",
+                self.result
+            );
+        }
+        self.result = ElectionResult::GovernanceFallback;
+    }
+
     pub fn complete(&mut self) -> (ElectionResult, u32) {
         let state = self
             .inner
             .take()
             .expect("At least one block must be processed in the ElectionRound; qed");
+        self.signed_submissions = 0;
         (std::mem::take(&mut self.result), state.round)
     }
 }
@@ -155,29 +280,30 @@ pub struct Client {
 }
 
 impl Client {
-    pub async fn new(url: Url) -> anyhow::Result<Self> {
-        let rpc = {
-            let rpc = subxt::backend::rpc::reconnecting_rpc_client::RpcClient::builder()
-                .max_request_size(u32::MAX)
-                .max_response_size(u32::MAX)
-                .retry_policy(
-                    ExponentialBackoff::from_millis(100)
-                        .max_delay(std::time::Duration::from_secs(10)),
-                )
-                .request_timeout(std::time::Duration::from_secs(600))
-                .build(url.as_str())
-                .await?;
-            subxt::backend::rpc::RpcClient::new(rpc)
-        };
+    /// Connect to the first reachable of `urls`, switching to the next ones if the connection is lost.
+    pub async fn new(urls: Vec<Url>) -> anyhow::Result<Self> {
+        let rpc = subxt::backend::rpc::RpcClient::new(FailoverRpcClient::new(urls).await?);
 
         let chain_api = ChainClient::from_rpc_client(rpc.clone()).await?;
         let rpc = RpcClient::new(rpc);
 
         let runtime_version = rpc.state_get_runtime_version(None).await?;
         let chain_name = match runtime_version.other.get("specName") {
-            Some(serde_json::Value::String(n)) => n.clone(),
-            Some(_) => return Err(anyhow::anyhow!("specName is not a string")),
-            None => return Err(anyhow::anyhow!("specName not found")),
+            Some(serde_json::Value::String(n)) => {
+                tracing::debug!(target: LOG_TARGET, "Chain name `{n}` from the runtime specName");
+                n.clone()
+            }
+            // Some runtimes don't expose the specName, fall back to the chain name of the node.
+            spec_name => {
+                let chain = rpc.system_chain().await.map_err(|e| {
+                    anyhow::anyhow!("specName is {spec_name:?} and system_chain failed: {e}")
+                })?;
+                tracing::info!(
+                    target: LOG_TARGET,
+                    "specName is {spec_name:?}, using the chain name `{chain}` from system_chain"
+                );
+                chain
+            }
         };
 
         Ok(Self {
@@ -204,7 +330,7 @@ impl Client {
 }
 
 /// The chain being used.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Chain {
     Westend,
     Kusama,
@@ -219,6 +345,23 @@ impl Chain {
             Self::Westend => "westend",
         }
     }
+
+    /// The SS58 prefix of the chain's addresses.
+    pub fn ss58_prefix(&self) -> u16 {
+        match self {
+            Self::Polkadot => 0,
+            Self::Kusama => 2,
+            Self::Westend => 42,
+        }
+    }
+
+    /// The number of decimals of the chain's native token.
+    pub fn token_decimals(&self) -> u8 {
+        match self {
+            Self::Polkadot => 10,
+            Self::Kusama | Self::Westend => 12,
+        }
+    }
 }
 
 impl std::fmt::Display for Chain {
@@ -240,6 +383,49 @@ impl std::str::FromStr for Chain {
     }
 }
 
+/// The SS58 prefix of the monitored chain, from `--chain` or read from the chain after connecting.
+///
+/// All addresses come from the same chain, so this is set once for the whole process.
+static SS58_PREFIX: OnceLock<u16> = OnceLock::new();
+
+/// The number of decimals of the monitored chain's native token, set like [`SS58_PREFIX`].
+static TOKEN_DECIMALS: OnceLock<u8> = OnceLock::new();
+
+/// Set the SS58 prefix addresses are serialized with, only the first call has an effect.
+pub fn set_ss58_prefix(prefix: u16) {
+    let _ = SS58_PREFIX.set(prefix);
+}
+
+/// Set the number of decimals amounts are formatted with, only the first call has an effect.
+pub fn set_token_decimals(decimals: u8) {
+    let _ = TOKEN_DECIMALS.set(decimals);
+}
+
+/// Use the SS58 prefix and token decimals of a known chain.
+pub fn set_chain(chain: Chain) {
+    set_ss58_prefix(chain.ss58_prefix());
+    set_token_decimals(chain.token_decimals());
+}
+
+/// Format an amount in planck as a decimal number of tokens without trailing zeros, e.g. `12.34`.
+///
+/// `None` if the token decimals of the chain aren't known.
+pub fn format_tokens(planck: u128) -> Option<String> {
+    let decimals = u32::from(*TOKEN_DECIMALS.get()?);
+    let unit = 10u128.checked_pow(decimals)?;
+    let (whole, fraction) = (planck / unit, planck % unit);
+
+    if fraction == 0 {
+        return Some(whole.to_string());
+    }
+
+    let fraction = format!("{fraction:0width$}", width = decimals as usize);
+    Some(format!("{whole}.{}", fraction.trim_end_matches('0')))
+}
+
+/// An account as the hex encoded public key, or `unsigned`.
+///
+/// This is stored in the database as is but serialized as SS58 once the chain's prefix is known.
 #[derive(Clone, Debug, PartialEq, OaSchema)]
 pub struct Address(String);
 
@@ -261,18 +447,58 @@ impl Address {
     pub fn from_bytes(bytes: &[u8]) -> Self {
         Self::signed(Hash::from_slice(bytes))
     }
+
+    pub fn from_account_id32(id: &AccountId32) -> Self {
+        Self::from_bytes(id.as_ref())
+    }
+
+    /// The SS58 encoding with the chain's prefix, `None` for `unsigned` or if the prefix isn't known.
+    pub fn to_ss58(&self) -> Option<String> {
+        let prefix = *SS58_PREFIX.get()?;
+        let hash = H256::from_str(self.0.strip_prefix("0x")?).ok()?;
+        let id = AccountId32::from(hash.to_fixed_bytes());
+
+        Some(id.to_ss58check_with_version(Ss58AddressFormat::custom(prefix)))
+    }
 }
 
 impl FromStr for Address {
     type Err = String;
 
+    /// Parses `unsigned`, an SS58 address of any network or a 32 byte public key in hex,
+    /// with or without the `0x` prefix.
     fn from_str(s: &str) -> Result<Self, String> {
-        Ok(match s.to_lowercase().trim() {
-            "unsigned" => Self::unsigned(),
-            other => H256::from_str(other)
-                .map(Self::signed)
-                .map_err(|e| format!("{e}"))?,
-        })
+        let raw = s.trim();
+
+        if raw.eq_ignore_ascii_case("unsigned") {
+            return Ok(Self::unsigned());
+        }
+
+        if let Ok((id, _)) = AccountId32::from_ss58check_with_version(raw) {
+            return Ok(Self::from_account_id32(&id));
+        }
+
+        let hex = raw
+            .strip_prefix("0x")
+            .or_else(|| raw.strip_prefix("0X"))
+            .unwrap_or(raw);
+
+        if hex.len() != 64 {
+            return Err(format!(
+                "Invalid address `{raw}`, expected an SS58 address or 32 bytes of hex but got {} characters",
+                hex.len()
+            ));
+        }
+
+        if let Some(c) = hex.chars().find(|c| !c.is_ascii_hexdigit()) {
+            return Err(format!(
+                "Invalid address `{raw}`, `{c}` is not a hex character"
+            ));
+        }
+
+        H256::from_str(hex)
+            .map(Self::signed)
+            .map_err(|e| format!("Invalid address `{raw}`: {e}"))
     }
 }
 
@@ -281,8 +507,9 @@ impl<'de> Deserialize<'de> for Address {
     where
         D: Deserializer<'de>,
     {
+        // Addresses are serialized as SS58, parse them back to the hex encoding that is stored.
         let s = String::deserialize(deserializer)?;
-        Ok(Address(s))
+        Address::from_str(&s).map_err(serde::de::Error::custom)
     }
 }
 
@@ -291,6 +518,121 @@ impl Serialize for Address {
     where
         S: Serializer,
     {
-        self.0.serialize(serializer)
+        match self.to_ss58() {
+            Some(ss58) => ss58.serialize(serializer),
+            None => self.0.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE_SS58: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+    const ALICE_HEX: &str = "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d";
+
+    fn hash(block: u64) -> Hash {
+        Hash::from_low_u64_be(block)
+    }
+
+    #[test]
+    fn skipped_block_finalizes_the_active_round() {
+        let mut state = ElectionRound::new();
+        for block in [10, 11, 13] {
+            assert_eq!(state.new_block(block, hash(block), 7), None);
+        }
+
+        // The election was finalized and rewarded in the skipped block 12.
+        assert_eq!(state.new_block(12, hash(12), 7), None);
+        let winner = Address::signed(hash(1));
+        state.add_winner(winner.clone());
+
+        // Only the blocks before the processed ones are read again.
+        assert_eq!(state.first_block(), Some(10));
+        assert!(state.is_processed(12, hash(12)));
+        assert!(!state.is_processed(12, hash(99)));
+        assert!(!state.is_processed(9, hash(9)));
+        assert_eq!(state.complete(), (ElectionResult::Signed(vec![winner]), 7));
+        assert!(!state.waiting_for_election_finalized());
+    }
+
+    #[test]
+    fn skipped_block_before_the_first_processed_block() {
+        let mut state = ElectionRound::new();
+        state.new_block(10, hash(10), 7);
+        state.new_block(8, hash(8), 7);
+
+        assert_eq!(state.first_block(), Some(8));
+        assert_eq!(state.new_block(8, hash(9), 7), Some(hash(8)));
+        assert_eq!(state.hash(8), Some(hash(9)));
+        assert_eq!(state.hash(9), None);
+    }
+
+    #[test]
+    fn round_state_roundtrip() {
+        let mut state = ElectionRound::new();
+        for block in 100..=102 {
+            state.new_block(block, hash(block), 7);
+        }
+        state.add_signed_submission();
+        state.add_signed_submission();
+        state.add_winner(Address::signed(hash(1)));
+
+        // As stored in and read from the `jsonb` column.
+        let json = serde_json::to_value(&state).unwrap().to_string();
+        let stored: ElectionRound =
+            serde_json::from_value(serde_json::from_str(&json).unwrap()).unwrap();
+
+        assert_eq!(stored, state);
+        assert_eq!(stored.round(), Some(7));
+        assert_eq!(stored.first_block(), Some(100));
+        assert_eq!(stored.signed_submissions(), 2);
+        assert!(stored.waiting_for_election_finalized());
+    }
+
+    #[test]
+    fn address_from_hex() {
+        let alice = Address::from_str(ALICE_HEX).unwrap();
+        assert_eq!(alice.to_string(), ALICE_HEX);
+
+        assert_eq!(Address::from_str(&ALICE_HEX[2..]), Ok(alice.clone()));
+        assert_eq!(
+            Address::from_str(&format!(" {} ", ALICE_HEX.to_uppercase())),
+            Ok(alice)
+        );
+    }
+
+    #[test]
+    fn address_from_ss58() {
+        assert_eq!(
+            Address::from_str(ALICE_SS58).map(|a| a.to_string()),
+            Ok(ALICE_HEX.to_string())
+        );
+    }
+
+    #[test]
+    fn address_unsigned() {
+        assert_eq!(Address::from_str("unsigned"), Ok(Address::unsigned()));
+        assert_eq!(Address::from_str("Unsigned"), Ok(Address::unsigned()));
+    }
+
+    #[test]
+    fn invalid_address() {
+        assert_eq!(
+            Address::from_str("0xABC"),
+            Err("Invalid address `0xABC`, expected an SS58 address or 32 bytes of hex but got 3 characters".to_string())
+        );
+
+        let not_hex = format!("0xG{}", &ALICE_HEX[3..]);
+        assert_eq!(
+            Address::from_str(&not_hex),
+            Err(format!(
+                "Invalid address `{not_hex}`, `G` is not a hex character"
+            ))
+        );
+
+        assert!(Address::from_str("").is_err());
+        assert!(Address::from_str(&ALICE_SS58[1..]).is_err());
     }
 }
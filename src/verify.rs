@@ -0,0 +1,39 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+use crate::db;
+use crate::types::{runtime, Client};
+use crate::LOG_TARGET;
+use url::Url;
+
+/// Check that the bundled metadata is compatible with the chain and that
+/// the database schema is up to date, without writing anything.
+pub async fn run(client: Client, postgres: Url) -> anyhow::Result<()> {
+    let mut ok = true;
+
+    if runtime::is_codegen_valid_for(&client.chain_api().metadata()) {
+        tracing::info!(target: LOG_TARGET, "The bundled metadata is compatible with the chain");
+    } else {
+        tracing::error!(target: LOG_TARGET, "The bundled metadata is not compatible with the chain, decoding may fail");
+        ok = false;
+    }
+
+    let db::MigrationStatus { pending, drifted } = db::migration_status(postgres).await?;
+    if pending.is_empty() {
+        tracing::info!(target: LOG_TARGET, "All database migrations are applied");
+    } else {
+        tracing::error!(target: LOG_TARGET, "Pending database migrations: {}", pending.join(", "));
+        ok = false;
+    }
+    if !drifted.is_empty() {
+        tracing::error!(target: LOG_TARGET, "Database migrations changed after they were applied: {}", drifted.join(", "));
+        ok = false;
+    }
+
+    if !ok {
+        return Err(anyhow::anyhow!("Verification failed"));
+    }
+
+    Ok(())
+}
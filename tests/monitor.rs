@@ -0,0 +1,86 @@
+// Copyright 2024 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Runs the monitor against a dev node with the election provider multi phase pallet, whose
+//! runtime must match `artifacts/metadata.scale`, and a throwaway database.
+//!
+//! A staking miner has to submit a signed solution to the node, e.g. `polkadot-staking-miner`.
+//! The test is ignored by default, run it with
+//! `POLKADOT_URL=ws://127.0.0.1:9944 POSTGRES_URL=postgres://postgres@localhost/monitor cargo test --test monitor -- --ignored`.
+
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use polkadot_staking_miner_monitor::db::Database;
+use polkadot_staking_miner_monitor::helpers::{get_block, get_finalized_number};
+use polkadot_staking_miner_monitor::monitor::process_block;
+use polkadot_staking_miner_monitor::types::{Client, ElectionRound};
+use polkadot_staking_miner_monitor::Address;
+use tokio::task::JoinSet;
+use url::Url;
+
+/// How long to wait for a round to be finalized.
+const TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+fn env_url(var: &str) -> Url {
+    let url = std::env::var(var).unwrap_or_else(|_| panic!("{var} must be set"));
+    url.parse()
+        .unwrap_or_else(|e| panic!("{var} is not a valid URL: {e}"))
+}
+
+/// Process the finalized blocks from the current head until the election of a round is recorded,
+/// returns that round.
+async fn process_until_election(client: &Client, db: &Database) -> anyhow::Result<u32> {
+    let mut state = ElectionRound::new();
+    let mut late_rewards = JoinSet::new();
+    let mut next = get_finalized_number(client.rpc()).await?;
+
+    loop {
+        if next > get_finalized_number(client.rpc()).await? {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            continue;
+        }
+
+        let block = get_block(client, next).await?;
+        let round = state.round();
+        process_block(client, db, &mut state, &block, 0, &mut late_rewards).await?;
+        next += 1;
+
+        if let Some(round) = round {
+            if db.get_election_for_round(round).await?.is_some() {
+                return Ok(round);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a dev node with a staking miner and a database, see the module docs"]
+async fn records_a_signed_round() {
+    let client = Client::new(vec![env_url("POLKADOT_URL")]).await.unwrap();
+    let db = Database::new(
+        env_url("POSTGRES_URL"),
+        NonZeroUsize::new(2).unwrap(),
+        false,
+    )
+    .await
+    .unwrap();
+
+    let round = tokio::time::timeout(TIMEOUT, process_until_election(&client, &db))
+        .await
+        .expect("no election was finalized in time")
+        .unwrap();
+
+    let election = db.get_election_for_round(round).await.unwrap().unwrap();
+    assert!(
+        !election.is_failed(),
+        "the election of round {round} failed"
+    );
+
+    let submissions = db.get_submissions_for_round(round).await.unwrap();
+    assert!(
+        submissions.iter().any(|s| s.who() != &Address::unsigned()),
+        "no signed submission was recorded in round {round}: {submissions:?}"
+    );
+}